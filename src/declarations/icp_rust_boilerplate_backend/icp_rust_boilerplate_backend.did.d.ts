@@ -3,45 +3,518 @@ import type { ActorMethod } from '@dfinity/agent';
 
 export interface Account {
   'id' : bigint,
+  'status' : AccountStatus,
   'holder_name' : string,
-  'balance' : number,
+  'balance' : bigint,
+  'owner' : Principal,
+  'held' : bigint,
+  'subaccount' : Uint8Array | number[],
   'created_at' : bigint,
+  'foreign_balances' : Array<[string, bigint]>,
 }
-export type Error = { 'NotFound' : { 'msg' : string } } |
+export type AccountStatus = { 'Closed' : null } |
+  { 'Active' : null } |
+  { 'Dormant' : null } |
+  { 'Frozen' : null };
+export interface AccrualFailure { 'account_id' : bigint, 'error' : Error }
+export interface Allowance {
+  'allowance' : bigint,
+  'expires_at' : [] | [bigint],
+}
+export interface AllowanceArgs {
+  'account' : IcrcAccount,
+  'spender' : IcrcAccount,
+}
+export interface ApproveArgs {
+  'fee' : [] | [bigint],
+  'memo' : [] | [Uint8Array | number[]],
+  'from_subaccount' : [] | [Uint8Array | number[]],
+  'created_at_time' : [] | [bigint],
+  'amount' : bigint,
+  'expected_allowance' : [] | [bigint],
+  'expires_at' : [] | [bigint],
+  'spender' : IcrcAccount,
+}
+export type ApproveError = {
+    'GenericError' : { 'message' : string, 'error_code' : bigint }
+  } |
+  { 'TemporarilyUnavailable' : null } |
+  { 'Duplicate' : { 'duplicate_of' : bigint } } |
+  { 'BadFee' : { 'expected_fee' : bigint } } |
+  { 'AllowanceChanged' : { 'current_allowance' : bigint } } |
+  { 'CreatedInFuture' : { 'ledger_time' : bigint } } |
+  { 'TooOld' : null } |
+  { 'Expired' : { 'ledger_time' : bigint } } |
+  { 'InsufficientFunds' : { 'balance' : bigint } };
+export interface ArchiveInfo {
+  'end' : bigint,
+  'canister_id' : Principal,
+  'start' : bigint,
+}
+export interface ArchivedBlocks {
+  'args' : Array<GetBlocksArgs>,
+  'callback' : [Principal, string],
+}
+export interface BatchResult {
+  'batch_id' : [] | [bigint],
+  'results' : Array<Result_3>,
+}
+export interface BlockWithId { 'id' : bigint, 'block' : Value }
+export interface CertifiedAccount {
+  'certificate' : [] | [Uint8Array | number[]],
+  'witness' : Uint8Array | number[],
+  'account' : Account,
+}
+export interface CertifiedBalance {
+  'certificate' : [] | [Uint8Array | number[]],
+  'available_balance' : bigint,
+  'balance' : bigint,
+  'witness' : Uint8Array | number[],
+}
+export interface CertifiedBalances {
+  'certificate' : [] | [Uint8Array | number[]],
+  'witness' : Uint8Array | number[],
+  'balances' : Array<CurrencyBalance>,
+}
+export interface CertifiedTransaction {
+  'certificate' : [] | [Uint8Array | number[]],
+  'transaction' : Transaction,
+  'witness' : Uint8Array | number[],
+}
+export interface Conversion { 'currency' : string, 'amount' : bigint }
+export interface Currency {
+  'decimals' : number,
+  'code' : string,
+  'name' : string,
+}
+export interface CurrencyBalance {
+  'decimals' : number,
+  'balance' : bigint,
+  'currency' : string,
+}
+export interface DataCertificate {
+  'certificate' : Uint8Array | number[],
+  'hash_tree' : Uint8Array | number[],
+}
+export type Direction = { 'Descending' : null } |
+  { 'Ascending' : null };
+export type EndCondition = { 'At' : bigint } |
+  { 'Never' : null } |
+  { 'AfterExecutions' : bigint };
+export type EntrySide = { 'Debit' : null } |
+  { 'Credit' : null };
+export type Error = { 'BalanceNotZero' : { 'msg' : string } } |
+  { 'SelfTransfer' : { 'msg' : string } } |
+  { 'InvalidBatch' : { 'msg' : string } } |
+  { 'UnbalancedEntry' : { 'msg' : string } } |
+  { 'TipUnavailable' : { 'msg' : string } } |
+  { 'InvalidStatusTransition' : { 'msg' : string } } |
+  { 'InvalidAmount' : { 'msg' : string } } |
+  { 'InvalidFeeSchedule' : { 'msg' : string } } |
+  { 'InvalidEscrow' : { 'msg' : string } } |
+  { 'InvalidReversal' : { 'msg' : string } } |
+  { 'Duplicate' : { 'existing_tx_id' : bigint } } |
+  { 'InvalidCreatedAtTime' : { 'msg' : string } } |
+  { 'InvalidCurrency' : { 'msg' : string } } |
+  { 'InvalidRepair' : { 'msg' : string } } |
+  { 'InvalidIdempotencyKey' : { 'msg' : string } } |
+  { 'NotFound' : { 'msg' : string } } |
+  { 'InvalidOverdraftTerms' : { 'msg' : string } } |
+  { 'InvalidSchedule' : { 'msg' : string } } |
+  { 'AccountNotActive' : { 'msg' : string } } |
+  { 'InvalidHold' : { 'msg' : string } } |
+  { 'Unauthorized' : { 'msg' : string } } |
+  { 'InvalidName' : { 'msg' : string } } |
+  { 'CurrencyMismatch' : { 'msg' : string } } |
+  { 'NameTooLong' : { 'msg' : string } } |
+  { 'InvalidOwner' : { 'msg' : string } } |
+  { 'BatchAborted' : { 'msg' : string } } |
+  { 'InvalidReference' : { 'msg' : string } } |
+  { 'ArithmeticOverflow' : { 'msg' : string } } |
   { 'InsufficientFunds' : { 'msg' : string } };
-export type Result = { 'Ok' : null } |
+export interface Escrow {
+  'id' : bigint,
+  'status' : EscrowStatus,
+  'arbiter' : Principal,
+  'hold_transaction_id' : bigint,
+  'deadline' : bigint,
+  'created_at' : bigint,
+  'payee_id' : bigint,
+  'payer_id' : bigint,
+  'settlement_transaction_id' : [] | [bigint],
+  'amount' : bigint,
+}
+export type EscrowStatus = { 'Refunded' : null } |
+  { 'Held' : null } |
+  { 'Released' : null };
+export type FeeRule = { 'Flat' : bigint } |
+  { 'Tiered' : Array<FeeTier> } |
+  { 'Percentage' : { 'basis_points' : number } };
+export interface FeeSchedule {
+  'rule' : FeeRule,
+  'minimum' : bigint,
+  'treasury_account_id' : [] | [bigint],
+  'maximum' : [] | [bigint],
+}
+export interface FeeTier {
+  'min_amount' : bigint,
+  'flat' : bigint,
+  'basis_points' : number,
+}
+export interface GetArchivesArgs { 'from' : [] | [Principal] }
+export interface GetBlocksArgs { 'start' : bigint, 'length' : bigint }
+export interface GetBlocksResult {
+  'log_length' : bigint,
+  'blocks' : Array<BlockWithId>,
+  'archived_blocks' : Array<ArchivedBlocks>,
+}
+export interface Hold {
+  'id' : bigint,
+  'account_id' : bigint,
+  'status' : HoldStatus,
+  'placed_by' : Principal,
+  'created_at' : bigint,
+  'amount' : bigint,
+  'expires_at' : bigint,
+  'capture_transaction_id' : [] | [bigint],
+}
+export type HoldStatus = { 'Active' : null } |
+  { 'Released' : null } |
+  { 'Captured' : null } |
+  { 'Expired' : null };
+export interface IcrcAccount {
+  'owner' : Principal,
+  'subaccount' : [] | [Uint8Array | number[]],
+}
+export interface InterestAccrual {
+  'failures' : Array<AccrualFailure>,
+  'charges' : Array<Transaction>,
+}
+export interface JournalEntry {
+  'transaction_id' : bigint,
+  'lines' : Array<JournalLine>,
+  'timestamp' : bigint,
+}
+export interface JournalLine {
+  'side' : EntrySide,
+  'currency' : [] | [string],
+  'account' : LedgerAccount,
+  'amount' : bigint,
+}
+export type LedgerAccount = { 'Customer' : bigint } |
+  { 'System' : SystemAccount };
+export type MetadataValue = { 'Int' : bigint } |
+  { 'Nat' : bigint } |
+  { 'Blob' : Uint8Array | number[] } |
+  { 'Text' : string };
+export interface OverdraftTerms {
+  'annual_rate_bps' : number,
+  'limit' : bigint,
+  'last_accrued_at' : bigint,
+}
+export interface OverdrawnAccount {
+  'account_id' : bigint,
+  'holder_name' : string,
+  'balance' : bigint,
+  'annual_rate_bps' : number,
+  'limit' : bigint,
+}
+export interface QuarantinedRecord {
+  'key' : bigint,
+  'kind' : RecordKind,
+  'error' : string,
+  'bytes' : Uint8Array | number[],
+  'quarantined_at' : bigint,
+}
+export type RecordKind = { 'Transaction' : null } |
+  { 'Account' : null };
+export interface RefundFailure {
+  'attempted_at' : bigint,
+  'attempt' : number,
+  'next_attempt_at' : bigint,
+  'error' : Error,
+}
+export type Result = { 'Ok' : InterestAccrual } |
   { 'Err' : Error };
 export type Result_1 = { 'Ok' : Account } |
   { 'Err' : Error };
-export type Result_2 = { 'Ok' : number } |
+export type Result_10 = { 'Ok' : Array<Hold> } |
   { 'Err' : Error };
-export type Result_3 = { 'Ok' : bigint } |
+export type Result_11 = { 'Ok' : TransactionPage } |
+  { 'Err' : Error };
+export type Result_12 = { 'Ok' : CertifiedBalances } |
+  { 'Err' : Error };
+export type Result_13 = { 'Ok' : [] | [RefundFailure] } |
+  { 'Err' : Error };
+export type Result_14 = { 'Ok' : JournalEntry } |
+  { 'Err' : Error };
+export type Result_15 = { 'Ok' : OverdraftTerms } |
+  { 'Err' : Error };
+export type Result_16 = { 'Ok' : ReversalSummary } |
+  { 'Err' : Error };
+export type Result_17 = { 'Ok' : CertifiedTransaction } |
+  { 'Err' : Error };
+export type Result_18 = { 'Ok' : TrialBalance } |
+  { 'Err' : Error };
+export type Result_19 = { 'Ok' : bigint } |
+  { 'Err' : TransferError };
+export type Result_2 = { 'Ok' : Schedule } |
+  { 'Err' : Error };
+export type Result_20 = { 'Ok' : bigint } |
+  { 'Err' : ApproveError };
+export type Result_21 = { 'Ok' : bigint } |
+  { 'Err' : TransferFromError };
+export type Result_22 = { 'Ok' : Hold } |
+  { 'Err' : Error };
+export type Result_23 = { 'Ok' : TransferQuote } |
+  { 'Err' : Error };
+export type Result_24 = { 'Ok' : Currency } |
+  { 'Err' : Error };
+export type Result_25 = { 'Ok' : BatchResult } |
+  { 'Err' : Error };
+export type Result_3 = { 'Ok' : Transaction } |
   { 'Err' : Error };
 export type Result_4 = { 'Ok' : Array<Transaction> } |
   { 'Err' : Error };
-export type Result_5 = { 'Ok' : Transaction } |
+export type Result_5 = { 'Ok' : Escrow } |
   { 'Err' : Error };
-export interface Transaction {
+export type Result_6 = { 'Ok' : null } |
+  { 'Err' : Error };
+export type Result_7 = { 'Ok' : CertifiedAccount } |
+  { 'Err' : Error };
+export type Result_8 = { 'Ok' : CertifiedBalance } |
+  { 'Err' : Error };
+export type Result_9 = { 'Ok' : bigint } |
+  { 'Err' : Error };
+export interface ReversalSummary {
+  'transaction_id' : bigint,
+  'original_amount' : bigint,
+  'reversal_ids' : BigUint64Array | bigint[],
+  'refundable' : bigint,
+  'reversed' : bigint,
+}
+export type Role = { 'Teller' : null } |
+  { 'Auditor' : null } |
+  { 'Admin' : null };
+export interface Schedule {
+  'id' : bigint,
+  'failures' : Array<ScheduleFailure>,
+  'status' : ScheduleStatus,
   'receiver_id' : bigint,
+  'interval' : [] | [bigint],
+  'attempt' : number,
+  'owner' : Principal,
+  'executions' : bigint,
+  'created_at' : bigint,
+  'end_condition' : EndCondition,
+  'last_transaction_id' : [] | [bigint],
+  'currency' : [] | [string],
+  'due_at' : bigint,
   'sender_id' : bigint,
+  'next_run_at' : bigint,
+  'amount' : bigint,
+}
+export interface ScheduleFailure {
+  'attempted_at' : bigint,
+  'attempt' : number,
+  'error' : Error,
+}
+export type ScheduleStatus = { 'Failed' : null } |
+  { 'Active' : null } |
+  { 'Cancelled' : null } |
+  { 'Completed' : null };
+export interface StandardRecord { 'url' : string, 'name' : string }
+export interface SupportedBlockType { 'url' : string, 'block_type' : string }
+export type SystemAccount = { 'Escrow' : null } |
+  { 'CashOut' : null } |
+  { 'Suspense' : null } |
+  { 'FeeIncome' : null } |
+  { 'Exchange' : null } |
+  { 'CashIn' : null };
+export interface Transaction {
+  'id' : bigint,
+  'to' : [] | [IcrcAccount],
+  'fee' : [] | [bigint],
+  'receiver_id' : [] | [bigint],
+  'from' : [] | [IcrcAccount],
+  'kind' : TransactionKind,
+  'memo' : [] | [Uint8Array | number[]],
+  'batch_id' : [] | [bigint],
+  'reference' : [] | [string],
+  'reverses' : [] | [bigint],
+  'currency' : [] | [string],
+  'sender_id' : [] | [bigint],
   'timestamp' : bigint,
-  'amount' : number,
+  'phash' : [] | [Uint8Array | number[]],
+  'amount' : bigint,
+  'expires_at' : [] | [bigint],
+  'conversion' : [] | [Conversion],
+  'spender' : [] | [IcrcAccount],
+}
+export type TransactionKind = { 'EscrowRelease' : null } |
+  { 'Deposit' : null } |
+  { 'EscrowRefund' : null } |
+  { 'EscrowHold' : null } |
+  { 'Approval' : null } |
+  { 'Withdrawal' : null } |
+  { 'Transfer' : null };
+export interface TransactionPage {
+  'next_cursor' : [] | [bigint],
+  'transactions' : Array<Transaction>,
+}
+export interface TransferArg {
+  'to' : IcrcAccount,
+  'fee' : [] | [bigint],
+  'memo' : [] | [Uint8Array | number[]],
+  'from_subaccount' : [] | [Uint8Array | number[]],
+  'created_at_time' : [] | [bigint],
+  'amount' : bigint,
+}
+export type TransferError = {
+    'GenericError' : { 'message' : string, 'error_code' : bigint }
+  } |
+  { 'TemporarilyUnavailable' : null } |
+  { 'BadBurn' : { 'min_burn_amount' : bigint } } |
+  { 'Duplicate' : { 'duplicate_of' : bigint } } |
+  { 'BadFee' : { 'expected_fee' : bigint } } |
+  { 'CreatedInFuture' : { 'ledger_time' : bigint } } |
+  { 'TooOld' : null } |
+  { 'InsufficientFunds' : { 'balance' : bigint } };
+export interface TransferFromArgs {
+  'to' : IcrcAccount,
+  'fee' : [] | [bigint],
+  'spender_subaccount' : [] | [Uint8Array | number[]],
+  'from' : IcrcAccount,
+  'memo' : [] | [Uint8Array | number[]],
+  'created_at_time' : [] | [bigint],
+  'amount' : bigint,
 }
+export type TransferFromError = {
+    'GenericError' : { 'message' : string, 'error_code' : bigint }
+  } |
+  { 'TemporarilyUnavailable' : null } |
+  { 'InsufficientAllowance' : { 'allowance' : bigint } } |
+  { 'BadBurn' : { 'min_burn_amount' : bigint } } |
+  { 'Duplicate' : { 'duplicate_of' : bigint } } |
+  { 'BadFee' : { 'expected_fee' : bigint } } |
+  { 'CreatedInFuture' : { 'ledger_time' : bigint } } |
+  { 'TooOld' : null } |
+  { 'InsufficientFunds' : { 'balance' : bigint } };
 export interface TransferPayload {
   'receiver_id' : bigint,
+  'currency' : [] | [string],
   'sender_id' : bigint,
-  'amount' : number,
+  'created_at_time' : [] | [bigint],
+  'amount' : bigint,
+  'conversion' : [] | [Conversion],
+  'idempotency_key' : [] | [string],
+}
+export interface TransferQuote {
+  'fee' : bigint,
+  'total' : bigint,
+  'treasury_account_id' : [] | [bigint],
+  'amount' : bigint,
+}
+export interface TrialBalance {
+  'lines' : Array<TrialBalanceLine>,
+  'currency' : string,
+  'total_credits' : bigint,
+  'total_debits' : bigint,
+}
+export interface TrialBalanceLine {
+  'credits' : bigint,
+  'debits' : bigint,
+  'account' : LedgerAccount,
 }
+export type Value = { 'Int' : bigint } |
+  { 'Map' : Array<[string, Value]> } |
+  { 'Nat' : bigint } |
+  { 'Blob' : Uint8Array | number[] } |
+  { 'Text' : string } |
+  { 'Array' : Array<Value> };
 export interface _SERVICE {
-  'create_account' : ActorMethod<[string, number], [] | [Account]>,
-  'delete_account' : ActorMethod<[bigint], Result>,
-  'get_account' : ActorMethod<[bigint], Result_1>,
-  'get_account_balance' : ActorMethod<[bigint], Result_2>,
-  'get_account_created_at' : ActorMethod<[bigint], Result_3>,
+  'accrue_overdraft_interest' : ActorMethod<[], Result>,
+  'assign_account_owner' : ActorMethod<[bigint, Principal], Result_1>,
+  'cancel_schedule' : ActorMethod<[bigint], Result_2>,
+  'capture_hold' : ActorMethod<[bigint, bigint, bigint], Result_3>,
+  'close_account' : ActorMethod<[bigint, bigint], Result_4>,
+  'create_account' : ActorMethod<[string, bigint], Result_1>,
+  'create_escrow' : ActorMethod<
+    [bigint, bigint, bigint, Principal, bigint],
+    Result_5
+  >,
+  'delete_account' : ActorMethod<[bigint], Result_6>,
+  'deposit' : ActorMethod<[bigint, bigint, string, [] | [string]], Result_3>,
+  'freeze_account' : ActorMethod<[bigint], Result_1>,
+  'get_account' : ActorMethod<[bigint], Result_7>,
+  'get_account_balance' : ActorMethod<[bigint], Result_8>,
+  'get_account_created_at' : ActorMethod<[bigint], Result_9>,
+  'get_account_holds' : ActorMethod<[bigint], Result_10>,
+  'get_account_transactions' : ActorMethod<
+    [bigint, [] | [bigint], number, Direction],
+    Result_11
+  >,
   'get_all_accounts' : ActorMethod<[], Array<Account>>,
   'get_all_transactions' : ActorMethod<[], Result_4>,
+  'get_balances' : ActorMethod<[bigint], Result_12>,
+  'get_escrow' : ActorMethod<[bigint], Result_5>,
+  'get_escrow_refund_failure' : ActorMethod<[bigint], Result_13>,
+  'get_fee_schedule' : ActorMethod<[], FeeSchedule>,
+  'get_journal_entry' : ActorMethod<[bigint], Result_14>,
+  'get_overdraft_terms' : ActorMethod<[bigint], Result_15>,
+  'get_overdrawn_accounts' : ActorMethod<[], Array<OverdrawnAccount>>,
+  'get_quarantined_records' : ActorMethod<[], Array<QuarantinedRecord>>,
   'get_receiver_account' : ActorMethod<[bigint], Result_1>,
+  'get_reversals' : ActorMethod<[bigint], Result_16>,
+  'get_roles' : ActorMethod<[Principal], Array<Role>>,
+  'get_schema_version' : ActorMethod<[], number>,
   'get_sender_account' : ActorMethod<[bigint], Result_1>,
-  'transfer_funds' : ActorMethod<[TransferPayload], Result_5>,
-  'update_account_holder_name' : ActorMethod<[bigint, string], Result>,
+  'get_transaction' : ActorMethod<[bigint], Result_17>,
+  'get_trial_balance' : ActorMethod<[[] | [string]], Result_18>,
+  'grant_role' : ActorMethod<[Principal, Role], Result_6>,
+  'icrc1_balance_of' : ActorMethod<[IcrcAccount], bigint>,
+  'icrc1_decimals' : ActorMethod<[], number>,
+  'icrc1_fee' : ActorMethod<[], bigint>,
+  'icrc1_metadata' : ActorMethod<[], Array<[string, MetadataValue]>>,
+  'icrc1_minting_account' : ActorMethod<[], [] | [IcrcAccount]>,
+  'icrc1_name' : ActorMethod<[], string>,
+  'icrc1_supported_standards' : ActorMethod<[], Array<StandardRecord>>,
+  'icrc1_symbol' : ActorMethod<[], string>,
+  'icrc1_total_supply' : ActorMethod<[], bigint>,
+  'icrc1_transfer' : ActorMethod<[TransferArg], Result_19>,
+  'icrc2_allowance' : ActorMethod<[AllowanceArgs], Allowance>,
+  'icrc2_approve' : ActorMethod<[ApproveArgs], Result_20>,
+  'icrc2_transfer_from' : ActorMethod<[TransferFromArgs], Result_21>,
+  'icrc3_get_archives' : ActorMethod<[GetArchivesArgs], Array<ArchiveInfo>>,
+  'icrc3_get_blocks' : ActorMethod<[Array<GetBlocksArgs>], GetBlocksResult>,
+  'icrc3_get_tip_certificate' : ActorMethod<[], [] | [DataCertificate]>,
+  'icrc3_supported_block_types' : ActorMethod<[], Array<SupportedBlockType>>,
+  'list_currencies' : ActorMethod<[], Array<Currency>>,
+  'list_escrows' : ActorMethod<[], Array<Escrow>>,
+  'list_schedules' : ActorMethod<[], Array<Schedule>>,
+  'mark_account_dormant' : ActorMethod<[bigint], Result_1>,
+  'open_currency_balance' : ActorMethod<[bigint, string], Result_1>,
+  'place_hold' : ActorMethod<[bigint, bigint, bigint], Result_22>,
+  'quote_transfer' : ActorMethod<[TransferPayload], Result_23>,
+  'reactivate_account' : ActorMethod<[bigint], Result_1>,
+  'refund_escrow' : ActorMethod<[bigint], Result_5>,
+  'register_currency' : ActorMethod<[Currency], Result_24>,
+  'release_escrow' : ActorMethod<[bigint], Result_5>,
+  'release_hold' : ActorMethod<[bigint], Result_22>,
+  'repair_account' : ActorMethod<[bigint, Account], Result_6>,
+  'repair_transaction' : ActorMethod<[bigint, Transaction], Result_6>,
+  'reverse_transaction' : ActorMethod<[bigint, bigint, string], Result_3>,
+  'revoke_role' : ActorMethod<[Principal, Role], Result_6>,
+  'scan_for_corrupt_records' : ActorMethod<[], bigint>,
+  'schedule_transfer' : ActorMethod<
+    [TransferPayload, bigint, [] | [bigint], EndCondition],
+    Result_2
+  >,
+  'set_fee_schedule' : ActorMethod<[FeeSchedule], Result_6>,
+  'set_overdraft_terms' : ActorMethod<[bigint, bigint, number], Result_15>,
+  'transfer_batch' : ActorMethod<[Array<TransferPayload>], Result_25>,
+  'transfer_funds' : ActorMethod<[TransferPayload], Result_3>,
+  'unfreeze_account' : ActorMethod<[bigint], Result_1>,
+  'update_account_holder_name' : ActorMethod<[bigint, string], Result_6>,
+  'withdraw' : ActorMethod<[bigint, bigint, string, [] | [string]], Result_3>,
 }
@@ -1,49 +1,615 @@
 export const idlFactory = ({ IDL }) => {
+  const GetBlocksResult = IDL.Rec();
+  const Value = IDL.Rec();
+  const Error = IDL.Variant({
+    'BalanceNotZero' : IDL.Record({ 'msg' : IDL.Text }),
+    'SelfTransfer' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidBatch' : IDL.Record({ 'msg' : IDL.Text }),
+    'UnbalancedEntry' : IDL.Record({ 'msg' : IDL.Text }),
+    'TipUnavailable' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidStatusTransition' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidAmount' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidFeeSchedule' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidEscrow' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidReversal' : IDL.Record({ 'msg' : IDL.Text }),
+    'Duplicate' : IDL.Record({ 'existing_tx_id' : IDL.Nat64 }),
+    'InvalidCreatedAtTime' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidCurrency' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidRepair' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidIdempotencyKey' : IDL.Record({ 'msg' : IDL.Text }),
+    'NotFound' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidOverdraftTerms' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidSchedule' : IDL.Record({ 'msg' : IDL.Text }),
+    'AccountNotActive' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidHold' : IDL.Record({ 'msg' : IDL.Text }),
+    'Unauthorized' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidName' : IDL.Record({ 'msg' : IDL.Text }),
+    'CurrencyMismatch' : IDL.Record({ 'msg' : IDL.Text }),
+    'NameTooLong' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidOwner' : IDL.Record({ 'msg' : IDL.Text }),
+    'BatchAborted' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidReference' : IDL.Record({ 'msg' : IDL.Text }),
+    'ArithmeticOverflow' : IDL.Record({ 'msg' : IDL.Text }),
+    'InsufficientFunds' : IDL.Record({ 'msg' : IDL.Text }),
+  });
+  const AccrualFailure = IDL.Record({
+    'account_id' : IDL.Nat64,
+    'error' : Error,
+  });
+  const IcrcAccount = IDL.Record({
+    'owner' : IDL.Principal,
+    'subaccount' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+  });
+  const TransactionKind = IDL.Variant({
+    'EscrowRelease' : IDL.Null,
+    'Deposit' : IDL.Null,
+    'EscrowRefund' : IDL.Null,
+    'EscrowHold' : IDL.Null,
+    'Approval' : IDL.Null,
+    'Withdrawal' : IDL.Null,
+    'Transfer' : IDL.Null,
+  });
+  const Conversion = IDL.Record({
+    'currency' : IDL.Text,
+    'amount' : IDL.Int64,
+  });
+  const Transaction = IDL.Record({
+    'id' : IDL.Nat64,
+    'to' : IDL.Opt(IcrcAccount),
+    'fee' : IDL.Opt(IDL.Int64),
+    'receiver_id' : IDL.Opt(IDL.Nat64),
+    'from' : IDL.Opt(IcrcAccount),
+    'kind' : TransactionKind,
+    'memo' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'batch_id' : IDL.Opt(IDL.Nat64),
+    'reference' : IDL.Opt(IDL.Text),
+    'reverses' : IDL.Opt(IDL.Nat64),
+    'currency' : IDL.Opt(IDL.Text),
+    'sender_id' : IDL.Opt(IDL.Nat64),
+    'timestamp' : IDL.Nat64,
+    'phash' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'amount' : IDL.Int64,
+    'expires_at' : IDL.Opt(IDL.Nat64),
+    'conversion' : IDL.Opt(Conversion),
+    'spender' : IDL.Opt(IcrcAccount),
+  });
+  const InterestAccrual = IDL.Record({
+    'failures' : IDL.Vec(AccrualFailure),
+    'charges' : IDL.Vec(Transaction),
+  });
+  const Result = IDL.Variant({ 'Ok' : InterestAccrual, 'Err' : Error });
+  const AccountStatus = IDL.Variant({
+    'Closed' : IDL.Null,
+    'Active' : IDL.Null,
+    'Dormant' : IDL.Null,
+    'Frozen' : IDL.Null,
+  });
   const Account = IDL.Record({
     'id' : IDL.Nat64,
+    'status' : AccountStatus,
     'holder_name' : IDL.Text,
-    'balance' : IDL.Float64,
+    'balance' : IDL.Int64,
+    'owner' : IDL.Principal,
+    'held' : IDL.Int64,
+    'subaccount' : IDL.Vec(IDL.Nat8),
     'created_at' : IDL.Nat64,
+    'foreign_balances' : IDL.Vec(IDL.Tuple(IDL.Text, IDL.Int64)),
   });
-  const Error = IDL.Variant({
-    'NotFound' : IDL.Record({ 'msg' : IDL.Text }),
-    'InsufficientFunds' : IDL.Record({ 'msg' : IDL.Text }),
-  });
-  const Result = IDL.Variant({ 'Ok' : IDL.Null, 'Err' : Error });
   const Result_1 = IDL.Variant({ 'Ok' : Account, 'Err' : Error });
-  const Result_2 = IDL.Variant({ 'Ok' : IDL.Float64, 'Err' : Error });
-  const Result_3 = IDL.Variant({ 'Ok' : IDL.Nat64, 'Err' : Error });
-  const Transaction = IDL.Record({
+  const ScheduleFailure = IDL.Record({
+    'attempted_at' : IDL.Nat64,
+    'attempt' : IDL.Nat32,
+    'error' : Error,
+  });
+  const ScheduleStatus = IDL.Variant({
+    'Failed' : IDL.Null,
+    'Active' : IDL.Null,
+    'Cancelled' : IDL.Null,
+    'Completed' : IDL.Null,
+  });
+  const EndCondition = IDL.Variant({
+    'At' : IDL.Nat64,
+    'Never' : IDL.Null,
+    'AfterExecutions' : IDL.Nat64,
+  });
+  const Schedule = IDL.Record({
+    'id' : IDL.Nat64,
+    'failures' : IDL.Vec(ScheduleFailure),
+    'status' : ScheduleStatus,
     'receiver_id' : IDL.Nat64,
+    'interval' : IDL.Opt(IDL.Nat64),
+    'attempt' : IDL.Nat32,
+    'owner' : IDL.Principal,
+    'executions' : IDL.Nat64,
+    'created_at' : IDL.Nat64,
+    'end_condition' : EndCondition,
+    'last_transaction_id' : IDL.Opt(IDL.Nat64),
+    'currency' : IDL.Opt(IDL.Text),
+    'due_at' : IDL.Nat64,
     'sender_id' : IDL.Nat64,
-    'timestamp' : IDL.Nat64,
-    'amount' : IDL.Float64,
+    'next_run_at' : IDL.Nat64,
+    'amount' : IDL.Int64,
   });
+  const Result_2 = IDL.Variant({ 'Ok' : Schedule, 'Err' : Error });
+  const Result_3 = IDL.Variant({ 'Ok' : Transaction, 'Err' : Error });
   const Result_4 = IDL.Variant({ 'Ok' : IDL.Vec(Transaction), 'Err' : Error });
+  const EscrowStatus = IDL.Variant({
+    'Refunded' : IDL.Null,
+    'Held' : IDL.Null,
+    'Released' : IDL.Null,
+  });
+  const Escrow = IDL.Record({
+    'id' : IDL.Nat64,
+    'status' : EscrowStatus,
+    'arbiter' : IDL.Principal,
+    'hold_transaction_id' : IDL.Nat64,
+    'deadline' : IDL.Nat64,
+    'created_at' : IDL.Nat64,
+    'payee_id' : IDL.Nat64,
+    'payer_id' : IDL.Nat64,
+    'settlement_transaction_id' : IDL.Opt(IDL.Nat64),
+    'amount' : IDL.Int64,
+  });
+  const Result_5 = IDL.Variant({ 'Ok' : Escrow, 'Err' : Error });
+  const Result_6 = IDL.Variant({ 'Ok' : IDL.Null, 'Err' : Error });
+  const CertifiedAccount = IDL.Record({
+    'certificate' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'witness' : IDL.Vec(IDL.Nat8),
+    'account' : Account,
+  });
+  const Result_7 = IDL.Variant({ 'Ok' : CertifiedAccount, 'Err' : Error });
+  const CertifiedBalance = IDL.Record({
+    'certificate' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'available_balance' : IDL.Int64,
+    'balance' : IDL.Int64,
+    'witness' : IDL.Vec(IDL.Nat8),
+  });
+  const Result_8 = IDL.Variant({ 'Ok' : CertifiedBalance, 'Err' : Error });
+  const Result_9 = IDL.Variant({ 'Ok' : IDL.Nat64, 'Err' : Error });
+  const HoldStatus = IDL.Variant({
+    'Active' : IDL.Null,
+    'Released' : IDL.Null,
+    'Captured' : IDL.Null,
+    'Expired' : IDL.Null,
+  });
+  const Hold = IDL.Record({
+    'id' : IDL.Nat64,
+    'account_id' : IDL.Nat64,
+    'status' : HoldStatus,
+    'placed_by' : IDL.Principal,
+    'created_at' : IDL.Nat64,
+    'amount' : IDL.Int64,
+    'expires_at' : IDL.Nat64,
+    'capture_transaction_id' : IDL.Opt(IDL.Nat64),
+  });
+  const Result_10 = IDL.Variant({ 'Ok' : IDL.Vec(Hold), 'Err' : Error });
+  const Direction = IDL.Variant({
+    'Descending' : IDL.Null,
+    'Ascending' : IDL.Null,
+  });
+  const TransactionPage = IDL.Record({
+    'next_cursor' : IDL.Opt(IDL.Nat64),
+    'transactions' : IDL.Vec(Transaction),
+  });
+  const Result_11 = IDL.Variant({ 'Ok' : TransactionPage, 'Err' : Error });
+  const CurrencyBalance = IDL.Record({
+    'decimals' : IDL.Nat8,
+    'balance' : IDL.Int64,
+    'currency' : IDL.Text,
+  });
+  const CertifiedBalances = IDL.Record({
+    'certificate' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'witness' : IDL.Vec(IDL.Nat8),
+    'balances' : IDL.Vec(CurrencyBalance),
+  });
+  const Result_12 = IDL.Variant({ 'Ok' : CertifiedBalances, 'Err' : Error });
+  const RefundFailure = IDL.Record({
+    'attempted_at' : IDL.Nat64,
+    'attempt' : IDL.Nat32,
+    'next_attempt_at' : IDL.Nat64,
+    'error' : Error,
+  });
+  const Result_13 = IDL.Variant({
+    'Ok' : IDL.Opt(RefundFailure),
+    'Err' : Error,
+  });
+  const FeeTier = IDL.Record({
+    'min_amount' : IDL.Int64,
+    'flat' : IDL.Int64,
+    'basis_points' : IDL.Nat32,
+  });
+  const FeeRule = IDL.Variant({
+    'Flat' : IDL.Int64,
+    'Tiered' : IDL.Vec(FeeTier),
+    'Percentage' : IDL.Record({ 'basis_points' : IDL.Nat32 }),
+  });
+  const FeeSchedule = IDL.Record({
+    'rule' : FeeRule,
+    'minimum' : IDL.Int64,
+    'treasury_account_id' : IDL.Opt(IDL.Nat64),
+    'maximum' : IDL.Opt(IDL.Int64),
+  });
+  const EntrySide = IDL.Variant({ 'Debit' : IDL.Null, 'Credit' : IDL.Null });
+  const SystemAccount = IDL.Variant({
+    'Escrow' : IDL.Null,
+    'CashOut' : IDL.Null,
+    'Suspense' : IDL.Null,
+    'FeeIncome' : IDL.Null,
+    'Exchange' : IDL.Null,
+    'CashIn' : IDL.Null,
+  });
+  const LedgerAccount = IDL.Variant({
+    'Customer' : IDL.Nat64,
+    'System' : SystemAccount,
+  });
+  const JournalLine = IDL.Record({
+    'side' : EntrySide,
+    'currency' : IDL.Opt(IDL.Text),
+    'account' : LedgerAccount,
+    'amount' : IDL.Int64,
+  });
+  const JournalEntry = IDL.Record({
+    'transaction_id' : IDL.Nat64,
+    'lines' : IDL.Vec(JournalLine),
+    'timestamp' : IDL.Nat64,
+  });
+  const Result_14 = IDL.Variant({ 'Ok' : JournalEntry, 'Err' : Error });
+  const OverdraftTerms = IDL.Record({
+    'annual_rate_bps' : IDL.Nat32,
+    'limit' : IDL.Int64,
+    'last_accrued_at' : IDL.Nat64,
+  });
+  const Result_15 = IDL.Variant({ 'Ok' : OverdraftTerms, 'Err' : Error });
+  const OverdrawnAccount = IDL.Record({
+    'account_id' : IDL.Nat64,
+    'holder_name' : IDL.Text,
+    'balance' : IDL.Int64,
+    'annual_rate_bps' : IDL.Nat32,
+    'limit' : IDL.Int64,
+  });
+  const RecordKind = IDL.Variant({
+    'Transaction' : IDL.Null,
+    'Account' : IDL.Null,
+  });
+  const QuarantinedRecord = IDL.Record({
+    'key' : IDL.Nat64,
+    'kind' : RecordKind,
+    'error' : IDL.Text,
+    'bytes' : IDL.Vec(IDL.Nat8),
+    'quarantined_at' : IDL.Nat64,
+  });
+  const ReversalSummary = IDL.Record({
+    'transaction_id' : IDL.Nat64,
+    'original_amount' : IDL.Int64,
+    'reversal_ids' : IDL.Vec(IDL.Nat64),
+    'refundable' : IDL.Int64,
+    'reversed' : IDL.Int64,
+  });
+  const Result_16 = IDL.Variant({ 'Ok' : ReversalSummary, 'Err' : Error });
+  const Role = IDL.Variant({
+    'Teller' : IDL.Null,
+    'Auditor' : IDL.Null,
+    'Admin' : IDL.Null,
+  });
+  const CertifiedTransaction = IDL.Record({
+    'certificate' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'transaction' : Transaction,
+    'witness' : IDL.Vec(IDL.Nat8),
+  });
+  const Result_17 = IDL.Variant({ 'Ok' : CertifiedTransaction, 'Err' : Error });
+  const TrialBalanceLine = IDL.Record({
+    'credits' : IDL.Int64,
+    'debits' : IDL.Int64,
+    'account' : LedgerAccount,
+  });
+  const TrialBalance = IDL.Record({
+    'lines' : IDL.Vec(TrialBalanceLine),
+    'currency' : IDL.Text,
+    'total_credits' : IDL.Int64,
+    'total_debits' : IDL.Int64,
+  });
+  const Result_18 = IDL.Variant({ 'Ok' : TrialBalance, 'Err' : Error });
+  const MetadataValue = IDL.Variant({
+    'Int' : IDL.Int,
+    'Nat' : IDL.Nat,
+    'Blob' : IDL.Vec(IDL.Nat8),
+    'Text' : IDL.Text,
+  });
+  const StandardRecord = IDL.Record({ 'url' : IDL.Text, 'name' : IDL.Text });
+  const TransferArg = IDL.Record({
+    'to' : IcrcAccount,
+    'fee' : IDL.Opt(IDL.Nat),
+    'memo' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'from_subaccount' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'created_at_time' : IDL.Opt(IDL.Nat64),
+    'amount' : IDL.Nat,
+  });
+  const TransferError = IDL.Variant({
+    'GenericError' : IDL.Record({
+      'message' : IDL.Text,
+      'error_code' : IDL.Nat,
+    }),
+    'TemporarilyUnavailable' : IDL.Null,
+    'BadBurn' : IDL.Record({ 'min_burn_amount' : IDL.Nat }),
+    'Duplicate' : IDL.Record({ 'duplicate_of' : IDL.Nat }),
+    'BadFee' : IDL.Record({ 'expected_fee' : IDL.Nat }),
+    'CreatedInFuture' : IDL.Record({ 'ledger_time' : IDL.Nat64 }),
+    'TooOld' : IDL.Null,
+    'InsufficientFunds' : IDL.Record({ 'balance' : IDL.Nat }),
+  });
+  const Result_19 = IDL.Variant({ 'Ok' : IDL.Nat, 'Err' : TransferError });
+  const AllowanceArgs = IDL.Record({
+    'account' : IcrcAccount,
+    'spender' : IcrcAccount,
+  });
+  const Allowance = IDL.Record({
+    'allowance' : IDL.Nat,
+    'expires_at' : IDL.Opt(IDL.Nat64),
+  });
+  const ApproveArgs = IDL.Record({
+    'fee' : IDL.Opt(IDL.Nat),
+    'memo' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'from_subaccount' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'created_at_time' : IDL.Opt(IDL.Nat64),
+    'amount' : IDL.Nat,
+    'expected_allowance' : IDL.Opt(IDL.Nat),
+    'expires_at' : IDL.Opt(IDL.Nat64),
+    'spender' : IcrcAccount,
+  });
+  const ApproveError = IDL.Variant({
+    'GenericError' : IDL.Record({
+      'message' : IDL.Text,
+      'error_code' : IDL.Nat,
+    }),
+    'TemporarilyUnavailable' : IDL.Null,
+    'Duplicate' : IDL.Record({ 'duplicate_of' : IDL.Nat }),
+    'BadFee' : IDL.Record({ 'expected_fee' : IDL.Nat }),
+    'AllowanceChanged' : IDL.Record({ 'current_allowance' : IDL.Nat }),
+    'CreatedInFuture' : IDL.Record({ 'ledger_time' : IDL.Nat64 }),
+    'TooOld' : IDL.Null,
+    'Expired' : IDL.Record({ 'ledger_time' : IDL.Nat64 }),
+    'InsufficientFunds' : IDL.Record({ 'balance' : IDL.Nat }),
+  });
+  const Result_20 = IDL.Variant({ 'Ok' : IDL.Nat, 'Err' : ApproveError });
+  const TransferFromArgs = IDL.Record({
+    'to' : IcrcAccount,
+    'fee' : IDL.Opt(IDL.Nat),
+    'spender_subaccount' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'from' : IcrcAccount,
+    'memo' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'created_at_time' : IDL.Opt(IDL.Nat64),
+    'amount' : IDL.Nat,
+  });
+  const TransferFromError = IDL.Variant({
+    'GenericError' : IDL.Record({
+      'message' : IDL.Text,
+      'error_code' : IDL.Nat,
+    }),
+    'TemporarilyUnavailable' : IDL.Null,
+    'InsufficientAllowance' : IDL.Record({ 'allowance' : IDL.Nat }),
+    'BadBurn' : IDL.Record({ 'min_burn_amount' : IDL.Nat }),
+    'Duplicate' : IDL.Record({ 'duplicate_of' : IDL.Nat }),
+    'BadFee' : IDL.Record({ 'expected_fee' : IDL.Nat }),
+    'CreatedInFuture' : IDL.Record({ 'ledger_time' : IDL.Nat64 }),
+    'TooOld' : IDL.Null,
+    'InsufficientFunds' : IDL.Record({ 'balance' : IDL.Nat }),
+  });
+  const Result_21 = IDL.Variant({ 'Ok' : IDL.Nat, 'Err' : TransferFromError });
+  const GetArchivesArgs = IDL.Record({ 'from' : IDL.Opt(IDL.Principal) });
+  const ArchiveInfo = IDL.Record({
+    'end' : IDL.Nat,
+    'canister_id' : IDL.Principal,
+    'start' : IDL.Nat,
+  });
+  const GetBlocksArgs = IDL.Record({ 'start' : IDL.Nat, 'length' : IDL.Nat });
+  Value.fill(
+    IDL.Variant({
+      'Int' : IDL.Int,
+      'Map' : IDL.Vec(IDL.Tuple(IDL.Text, Value)),
+      'Nat' : IDL.Nat,
+      'Blob' : IDL.Vec(IDL.Nat8),
+      'Text' : IDL.Text,
+      'Array' : IDL.Vec(Value),
+    })
+  );
+  const BlockWithId = IDL.Record({ 'id' : IDL.Nat, 'block' : Value });
+  const ArchivedBlocks = IDL.Record({
+    'args' : IDL.Vec(GetBlocksArgs),
+    'callback' : IDL.Func(
+        [IDL.Vec(GetBlocksArgs)],
+        [GetBlocksResult],
+        ['query'],
+      ),
+  });
+  GetBlocksResult.fill(
+    IDL.Record({
+      'log_length' : IDL.Nat,
+      'blocks' : IDL.Vec(BlockWithId),
+      'archived_blocks' : IDL.Vec(ArchivedBlocks),
+    })
+  );
+  const DataCertificate = IDL.Record({
+    'certificate' : IDL.Vec(IDL.Nat8),
+    'hash_tree' : IDL.Vec(IDL.Nat8),
+  });
+  const SupportedBlockType = IDL.Record({
+    'url' : IDL.Text,
+    'block_type' : IDL.Text,
+  });
+  const Currency = IDL.Record({
+    'decimals' : IDL.Nat8,
+    'code' : IDL.Text,
+    'name' : IDL.Text,
+  });
+  const Result_22 = IDL.Variant({ 'Ok' : Hold, 'Err' : Error });
   const TransferPayload = IDL.Record({
     'receiver_id' : IDL.Nat64,
+    'currency' : IDL.Opt(IDL.Text),
     'sender_id' : IDL.Nat64,
-    'amount' : IDL.Float64,
+    'created_at_time' : IDL.Opt(IDL.Nat64),
+    'amount' : IDL.Int64,
+    'conversion' : IDL.Opt(Conversion),
+    'idempotency_key' : IDL.Opt(IDL.Text),
+  });
+  const TransferQuote = IDL.Record({
+    'fee' : IDL.Int64,
+    'total' : IDL.Int64,
+    'treasury_account_id' : IDL.Opt(IDL.Nat64),
+    'amount' : IDL.Int64,
+  });
+  const Result_23 = IDL.Variant({ 'Ok' : TransferQuote, 'Err' : Error });
+  const Result_24 = IDL.Variant({ 'Ok' : Currency, 'Err' : Error });
+  const BatchResult = IDL.Record({
+    'batch_id' : IDL.Opt(IDL.Nat64),
+    'results' : IDL.Vec(Result_3),
   });
-  const Result_5 = IDL.Variant({ 'Ok' : Transaction, 'Err' : Error });
+  const Result_25 = IDL.Variant({ 'Ok' : BatchResult, 'Err' : Error });
   return IDL.Service({
-    'create_account' : IDL.Func(
-        [IDL.Text, IDL.Float64],
-        [IDL.Opt(Account)],
+    'accrue_overdraft_interest' : IDL.Func([], [Result], []),
+    'assign_account_owner' : IDL.Func(
+        [IDL.Nat64, IDL.Principal],
+        [Result_1],
+        [],
+      ),
+    'cancel_schedule' : IDL.Func([IDL.Nat64], [Result_2], []),
+    'capture_hold' : IDL.Func(
+        [IDL.Nat64, IDL.Int64, IDL.Nat64],
+        [Result_3],
+        [],
+      ),
+    'close_account' : IDL.Func([IDL.Nat64, IDL.Nat64], [Result_4], []),
+    'create_account' : IDL.Func([IDL.Text, IDL.Int64], [Result_1], []),
+    'create_escrow' : IDL.Func(
+        [IDL.Nat64, IDL.Nat64, IDL.Int64, IDL.Principal, IDL.Nat64],
+        [Result_5],
+        [],
+      ),
+    'delete_account' : IDL.Func([IDL.Nat64], [Result_6], []),
+    'deposit' : IDL.Func(
+        [IDL.Nat64, IDL.Int64, IDL.Text, IDL.Opt(IDL.Text)],
+        [Result_3],
         [],
       ),
-    'delete_account' : IDL.Func([IDL.Nat64], [Result], []),
-    'get_account' : IDL.Func([IDL.Nat64], [Result_1], ['query']),
-    'get_account_balance' : IDL.Func([IDL.Nat64], [Result_2], ['query']),
-    'get_account_created_at' : IDL.Func([IDL.Nat64], [Result_3], ['query']),
+    'freeze_account' : IDL.Func([IDL.Nat64], [Result_1], []),
+    'get_account' : IDL.Func([IDL.Nat64], [Result_7], ['query']),
+    'get_account_balance' : IDL.Func([IDL.Nat64], [Result_8], ['query']),
+    'get_account_created_at' : IDL.Func([IDL.Nat64], [Result_9], ['query']),
+    'get_account_holds' : IDL.Func([IDL.Nat64], [Result_10], ['query']),
+    'get_account_transactions' : IDL.Func(
+        [IDL.Nat64, IDL.Opt(IDL.Nat64), IDL.Nat32, Direction],
+        [Result_11],
+        ['query'],
+      ),
     'get_all_accounts' : IDL.Func([], [IDL.Vec(Account)], ['query']),
     'get_all_transactions' : IDL.Func([], [Result_4], ['query']),
+    'get_balances' : IDL.Func([IDL.Nat64], [Result_12], ['query']),
+    'get_escrow' : IDL.Func([IDL.Nat64], [Result_5], ['query']),
+    'get_escrow_refund_failure' : IDL.Func([IDL.Nat64], [Result_13], ['query']),
+    'get_fee_schedule' : IDL.Func([], [FeeSchedule], ['query']),
+    'get_journal_entry' : IDL.Func([IDL.Nat64], [Result_14], ['query']),
+    'get_overdraft_terms' : IDL.Func([IDL.Nat64], [Result_15], ['query']),
+    'get_overdrawn_accounts' : IDL.Func(
+        [],
+        [IDL.Vec(OverdrawnAccount)],
+        ['query'],
+      ),
+    'get_quarantined_records' : IDL.Func(
+        [],
+        [IDL.Vec(QuarantinedRecord)],
+        ['query'],
+      ),
     'get_receiver_account' : IDL.Func([IDL.Nat64], [Result_1], ['query']),
+    'get_reversals' : IDL.Func([IDL.Nat64], [Result_16], ['query']),
+    'get_roles' : IDL.Func([IDL.Principal], [IDL.Vec(Role)], ['query']),
+    'get_schema_version' : IDL.Func([], [IDL.Nat32], ['query']),
     'get_sender_account' : IDL.Func([IDL.Nat64], [Result_1], ['query']),
-    'transfer_funds' : IDL.Func([TransferPayload], [Result_5], []),
+    'get_transaction' : IDL.Func([IDL.Nat64], [Result_17], ['query']),
+    'get_trial_balance' : IDL.Func([IDL.Opt(IDL.Text)], [Result_18], ['query']),
+    'grant_role' : IDL.Func([IDL.Principal, Role], [Result_6], []),
+    'icrc1_balance_of' : IDL.Func([IcrcAccount], [IDL.Nat], ['query']),
+    'icrc1_decimals' : IDL.Func([], [IDL.Nat8], ['query']),
+    'icrc1_fee' : IDL.Func([], [IDL.Nat], ['query']),
+    'icrc1_metadata' : IDL.Func(
+        [],
+        [IDL.Vec(IDL.Tuple(IDL.Text, MetadataValue))],
+        ['query'],
+      ),
+    'icrc1_minting_account' : IDL.Func([], [IDL.Opt(IcrcAccount)], ['query']),
+    'icrc1_name' : IDL.Func([], [IDL.Text], ['query']),
+    'icrc1_supported_standards' : IDL.Func(
+        [],
+        [IDL.Vec(StandardRecord)],
+        ['query'],
+      ),
+    'icrc1_symbol' : IDL.Func([], [IDL.Text], ['query']),
+    'icrc1_total_supply' : IDL.Func([], [IDL.Nat], ['query']),
+    'icrc1_transfer' : IDL.Func([TransferArg], [Result_19], []),
+    'icrc2_allowance' : IDL.Func([AllowanceArgs], [Allowance], ['query']),
+    'icrc2_approve' : IDL.Func([ApproveArgs], [Result_20], []),
+    'icrc2_transfer_from' : IDL.Func([TransferFromArgs], [Result_21], []),
+    'icrc3_get_archives' : IDL.Func(
+        [GetArchivesArgs],
+        [IDL.Vec(ArchiveInfo)],
+        ['query'],
+      ),
+    'icrc3_get_blocks' : IDL.Func(
+        [IDL.Vec(GetBlocksArgs)],
+        [GetBlocksResult],
+        ['query'],
+      ),
+    'icrc3_get_tip_certificate' : IDL.Func(
+        [],
+        [IDL.Opt(DataCertificate)],
+        ['query'],
+      ),
+    'icrc3_supported_block_types' : IDL.Func(
+        [],
+        [IDL.Vec(SupportedBlockType)],
+        ['query'],
+      ),
+    'list_currencies' : IDL.Func([], [IDL.Vec(Currency)], ['query']),
+    'list_escrows' : IDL.Func([], [IDL.Vec(Escrow)], ['query']),
+    'list_schedules' : IDL.Func([], [IDL.Vec(Schedule)], ['query']),
+    'mark_account_dormant' : IDL.Func([IDL.Nat64], [Result_1], []),
+    'open_currency_balance' : IDL.Func([IDL.Nat64, IDL.Text], [Result_1], []),
+    'place_hold' : IDL.Func([IDL.Nat64, IDL.Int64, IDL.Nat64], [Result_22], []),
+    'quote_transfer' : IDL.Func([TransferPayload], [Result_23], ['query']),
+    'reactivate_account' : IDL.Func([IDL.Nat64], [Result_1], []),
+    'refund_escrow' : IDL.Func([IDL.Nat64], [Result_5], []),
+    'register_currency' : IDL.Func([Currency], [Result_24], []),
+    'release_escrow' : IDL.Func([IDL.Nat64], [Result_5], []),
+    'release_hold' : IDL.Func([IDL.Nat64], [Result_22], []),
+    'repair_account' : IDL.Func([IDL.Nat64, Account], [Result_6], []),
+    'repair_transaction' : IDL.Func([IDL.Nat64, Transaction], [Result_6], []),
+    'reverse_transaction' : IDL.Func(
+        [IDL.Nat64, IDL.Int64, IDL.Text],
+        [Result_3],
+        [],
+      ),
+    'revoke_role' : IDL.Func([IDL.Principal, Role], [Result_6], []),
+    'scan_for_corrupt_records' : IDL.Func([], [IDL.Nat64], []),
+    'schedule_transfer' : IDL.Func(
+        [TransferPayload, IDL.Nat64, IDL.Opt(IDL.Nat64), EndCondition],
+        [Result_2],
+        [],
+      ),
+    'set_fee_schedule' : IDL.Func([FeeSchedule], [Result_6], []),
+    'set_overdraft_terms' : IDL.Func(
+        [IDL.Nat64, IDL.Int64, IDL.Nat32],
+        [Result_15],
+        [],
+      ),
+    'transfer_batch' : IDL.Func([IDL.Vec(TransferPayload)], [Result_25], []),
+    'transfer_funds' : IDL.Func([TransferPayload], [Result_3], []),
+    'unfreeze_account' : IDL.Func([IDL.Nat64], [Result_1], []),
     'update_account_holder_name' : IDL.Func(
         [IDL.Nat64, IDL.Text],
-        [Result],
+        [Result_6],
+        [],
+      ),
+    'withdraw' : IDL.Func(
+        [IDL.Nat64, IDL.Int64, IDL.Text, IDL.Opt(IDL.Text)],
+        [Result_3],
         [],
       ),
   });
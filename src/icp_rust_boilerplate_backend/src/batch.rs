@@ -0,0 +1,176 @@
+use crate::money::Money;
+use crate::{
+    _get_account, currency, do_transfer, ensure_owned_by, fees, get_account_or_not_found,
+    lifecycle, validation, Account, Error, IdCell, Transaction, TransferPayload, MEMORY_MANAGER,
+};
+use candid::Principal;
+use ic_cdk::api::caller;
+use ic_stable_structures::memory_manager::MemoryId;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// Maximum number of legs in a single batch.
+pub const MAX_BATCH_LEGS: usize = 500;
+
+/// Represents the outcome of a batch of transfers, with one result per leg in request order.
+///
+/// The batch ID is only set if every leg succeeded and the batch was applied; otherwise no
+/// leg was applied and each result says whether that leg failed or was merely aborted.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub batch_id: Option<u64>,
+    pub results: Vec<Result<Transaction, Error>>,
+}
+
+// Thread-local storage for the batch ID counter
+thread_local! {
+    static BATCH_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(25))), 0)
+            .expect("Cannot create a batch ID counter")
+    );
+}
+
+// Helper function to read an account as left by the legs simulated so far
+fn load(id: u64, working: &BTreeMap<u64, Account>) -> Result<Account, Error> {
+    working
+        .get(&id)
+        .cloned()
+        .or_else(|| _get_account(&id))
+        .ok_or_else(|| Error::NotFound {
+            msg: format!("Account with id={} not found.", id),
+        })
+}
+
+// Helper function to check one leg against the working copies of the accounts and apply it
+// to them, returning the details of the transfer it will make
+//
+// The checks mirror those of `transfer_funds`. The working copies are only changed if the
+// leg passes every check.
+fn simulate(
+    owner: &Principal,
+    payload: &TransferPayload,
+    working: &mut BTreeMap<u64, Account>,
+) -> Result<Transaction, Error> {
+    validation::validate_transfer(payload)?;
+    if payload.idempotency_key.is_some() || payload.created_at_time.is_some() {
+        return Err(Error::InvalidBatch {
+            msg: "Batch legs do not take an idempotency key or creation time.".to_string(),
+        });
+    }
+    let currency = currency::check_transfer(owner, payload)?;
+    let fee = fees::quote_in(&currency, payload.amount)?.fee;
+    let total = payload.amount.checked_add(fee)?;
+    let sent = currency::code_of(&currency);
+    let (received, received_amount) = match &payload.conversion {
+        Some(conversion) => (conversion.currency.as_str(), conversion.amount),
+        None => (sent, payload.amount),
+    };
+
+    let mut sender = load(payload.sender_id, working)?;
+    let mut receiver = load(payload.receiver_id, working)?;
+    ensure_owned_by(&sender, owner)?;
+    lifecycle::ensure_can_send(&sender)?;
+    lifecycle::ensure_can_receive(&receiver)?;
+    if currency::spendable_in(&sender, sent)? < total {
+        return Err(Error::InsufficientFunds {
+            msg: "Insufficient funds in the sender's account.".to_string(),
+        });
+    }
+    currency::debit(&mut sender, sent, total)?;
+    currency::credit(&mut receiver, received, received_amount)?;
+
+    // The treasury may be one of the two accounts, so it is credited on top of their updates
+    let mut updated = BTreeMap::from([(sender.id, sender), (receiver.id, receiver)]);
+    if fee > Money::ZERO {
+        let treasury_id = fees::treasury_account()?.id;
+        let mut treasury = match updated.remove(&treasury_id) {
+            Some(account) => account,
+            None => load(treasury_id, working)?,
+        };
+        lifecycle::ensure_can_receive(&treasury)?;
+        treasury.balance = treasury.balance.checked_add(fee)?;
+        updated.insert(treasury_id, treasury);
+    }
+    working.extend(updated);
+    Ok(Transaction {
+        amount: payload.amount,
+        fee: (fee > Money::ZERO).then_some(fee),
+        currency,
+        conversion: payload.conversion.clone(),
+        ..Default::default()
+    })
+}
+
+/// Applies a batch of transfers from the caller's accounts atomically: either every leg
+/// succeeds, or none is applied.
+///
+/// Legs are checked in order, each against the balances left by the legs before it, so a leg
+/// may spend funds received earlier in the same batch.
+#[ic_cdk::update]
+fn transfer_batch(legs: Vec<TransferPayload>) -> Result<BatchResult, Error> {
+    if legs.is_empty() || legs.len() > MAX_BATCH_LEGS {
+        return Err(Error::InvalidBatch {
+            msg: format!(
+                "A batch must have between 1 and {} legs, got {}.",
+                MAX_BATCH_LEGS,
+                legs.len()
+            ),
+        });
+    }
+
+    // Check every leg before any state is touched
+    let owner = caller();
+    let mut working = BTreeMap::new();
+    let checked: Vec<Result<Transaction, Error>> = legs
+        .iter()
+        .map(|payload| simulate(&owner, payload, &mut working))
+        .collect();
+    if checked.iter().any(|result| result.is_err()) {
+        let results = checked
+            .into_iter()
+            .enumerate()
+            .map(|(index, result)| match result {
+                Ok(_) => Err(Error::BatchAborted {
+                    msg: format!(
+                        "Leg {} is valid but another leg of the batch failed.",
+                        index
+                    ),
+                }),
+                Err(error) => Err(error),
+            })
+            .collect();
+        return Ok(BatchResult {
+            batch_id: None,
+            results,
+        });
+    }
+
+    // Every leg is known to succeed, so a failure here would be a bug; it is reported in the
+    // leg's result rather than trapping
+    let batch_id = BATCH_ID_COUNTER
+        .with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter.borrow_mut().set(current_value + 1)
+        })
+        .expect("Cannot increment batch ID counter");
+    let results = legs
+        .iter()
+        .zip(checked)
+        .map(|(payload, details)| {
+            let sender = get_account_or_not_found(payload.sender_id)?;
+            let receiver = get_account_or_not_found(payload.receiver_id)?;
+            do_transfer(
+                sender,
+                receiver,
+                Transaction {
+                    batch_id: Some(batch_id),
+                    ..details?
+                },
+            )
+        })
+        .collect();
+    Ok(BatchResult {
+        batch_id: Some(batch_id),
+        results,
+    })
+}
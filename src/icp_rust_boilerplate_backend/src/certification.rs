@@ -0,0 +1,186 @@
+use crate::currency::BASE_CURRENCY;
+use crate::icrc3::{self, Hash};
+use crate::{all_accounts, transactions_in, Account};
+use candid::Nat;
+use ic_cdk::api::set_certified_data;
+use ic_certification::{fork, labeled, leaf, pruned, AsHashTree, HashTree, RbTree};
+use serde::Serialize;
+use std::cell::RefCell;
+
+/// Number of most recent transactions whose block hashes are kept in the certified tree.
+pub const CERTIFIED_TRANSACTIONS: u64 = 1000;
+
+/// Represents the certified balances of one account, keyed by currency code.
+type AccountBalances = RbTree<Vec<u8>, Vec<u8>>;
+
+// Helper function to build the certified subtree of an account's balances in every currency
+fn account_balances(account: &Account) -> AccountBalances {
+    std::iter::once((BASE_CURRENCY.to_string(), account.balance))
+        .chain(account.foreign_balances.clone())
+        .map(|(code, balance)| {
+            (
+                code.into_bytes(),
+                balance.minor_units().to_be_bytes().to_vec(),
+            )
+        })
+        .collect()
+}
+
+// Thread-local state of the certified tree, rebuilt from stable memory after an upgrade
+//
+// The tree has four labels at its root:
+// - `balances`: account ID (8 bytes, big-endian) to a subtree from each currency code the
+//   account holds (ASCII) to its balance in minor units (8 bytes, big-endian)
+// - `last_block_hash` and `last_block_index`: the tip of the ICRC-3 log
+// - `transactions`: transaction ID (8 bytes, big-endian) to the hash of its ICRC-3 block
+thread_local! {
+    static BALANCES: RefCell<RbTree<[u8; 8], AccountBalances>> = const { RefCell::new(RbTree::new()) };
+
+    static RECENT_TRANSACTIONS: RefCell<RbTree<[u8; 8], Hash>> = const { RefCell::new(RbTree::new()) };
+
+    static TIP: RefCell<Option<(u64, Hash)>> = const { RefCell::new(None) };
+}
+
+// Helper function to build the tip subtrees from the last block index and hash
+fn tip_trees(tip: Option<(u64, Hash)>) -> (HashTree, HashTree) {
+    match tip {
+        Some((index, hash)) => {
+            let mut encoded_index = Vec::new();
+            Nat::from(index)
+                .encode(&mut encoded_index)
+                .expect("Cannot LEB128-encode a Nat");
+            (
+                labeled("last_block_hash", leaf(hash.to_vec())),
+                labeled("last_block_index", leaf(encoded_index)),
+            )
+        }
+        None => (
+            labeled("last_block_hash", leaf(Vec::new())),
+            labeled("last_block_index", leaf(Vec::new())),
+        ),
+    }
+}
+
+// Helper function to assemble the root tree from its four labeled subtrees, in label order
+fn root_tree(balances: HashTree, tip: (HashTree, HashTree), transactions: HashTree) -> HashTree {
+    let (last_block_hash, last_block_index) = tip;
+    fork(
+        fork(labeled("balances", balances), last_block_hash),
+        fork(last_block_index, labeled("transactions", transactions)),
+    )
+}
+
+// Helper function to replace the balances subtree with its root hash
+fn pruned_balances() -> HashTree {
+    BALANCES.with(|b| pruned(b.borrow().root_hash()))
+}
+
+// Helper function to replace the transactions subtree with its root hash
+fn pruned_transactions() -> HashTree {
+    RECENT_TRANSACTIONS.with(|t| pruned(t.borrow().root_hash()))
+}
+
+// Helper function to replace both tip subtrees with their root hashes
+fn pruned_tip() -> (HashTree, HashTree) {
+    let (last_block_hash, last_block_index) = tip_trees(TIP.with(|t| *t.borrow()));
+    (
+        pruned(last_block_hash.digest()),
+        pruned(last_block_index.digest()),
+    )
+}
+
+// Helper function to publish the root hash of the tree as the canister's certified data
+fn update_certified_data() {
+    let tip = TIP.with(|t| *t.borrow());
+    let root = root_tree(pruned_balances(), tip_trees(tip), pruned_transactions());
+    set_certified_data(&root.digest());
+}
+
+/// Certifies the current balances of an account in every currency it holds; must be called
+/// whenever an account is stored.
+pub fn certify_account(account: &Account) {
+    BALANCES.with(|b| {
+        b.borrow_mut()
+            .insert(account.id.to_be_bytes(), account_balances(account))
+    });
+    update_certified_data();
+}
+
+/// Removes a deleted account from the certified tree.
+pub fn uncertify_account(id: u64) {
+    BALANCES.with(|b| b.borrow_mut().delete(&id.to_be_bytes()));
+    update_certified_data();
+}
+
+/// Certifies a newly appended block as the tip of the log, evicting blocks that are no longer recent.
+pub fn certify_transaction(id: u64, hash: Hash) {
+    RECENT_TRANSACTIONS.with(|t| {
+        let mut t = t.borrow_mut();
+        t.insert(id.to_be_bytes(), hash);
+        if let Some(evicted) = id.checked_sub(CERTIFIED_TRANSACTIONS) {
+            t.delete(&evicted.to_be_bytes());
+        }
+    });
+    TIP.with(|t| *t.borrow_mut() = Some((id, hash)));
+    update_certified_data();
+}
+
+/// Rebuilds the certified tree from stable memory, which is required after an upgrade.
+pub fn rebuild() {
+    BALANCES.with(|b| {
+        let mut b = b.borrow_mut();
+        *b = RbTree::new();
+        for account in all_accounts() {
+            b.insert(account.id.to_be_bytes(), account_balances(&account));
+        }
+    });
+
+    let tip = icrc3::tip();
+    RECENT_TRANSACTIONS.with(|t| {
+        let mut t = t.borrow_mut();
+        *t = RbTree::new();
+        if let Some((last_id, _)) = tip {
+            let first_id = last_id.saturating_sub(CERTIFIED_TRANSACTIONS - 1);
+            for transaction in transactions_in(first_id..) {
+                let block = icrc3::transaction_to_block(&transaction);
+                t.insert(transaction.id.to_be_bytes(), icrc3::hash_value(&block));
+            }
+        }
+    });
+    TIP.with(|t| *t.borrow_mut() = tip);
+    update_certified_data();
+}
+
+/// Returns the witness for the balances of an account in every currency it holds, or a proof
+/// of its absence.
+pub fn balance_witness(id: u64) -> HashTree {
+    let balances = BALANCES.with(|b| b.borrow().witness(&id.to_be_bytes()));
+    root_tree(balances, pruned_tip(), pruned_transactions())
+}
+
+/// Returns the witness for a recent transaction, or a proof of its absence.
+pub fn transaction_witness(id: u64) -> HashTree {
+    let transactions = RECENT_TRANSACTIONS.with(|t| t.borrow().witness(&id.to_be_bytes()));
+    root_tree(pruned_balances(), pruned_tip(), transactions)
+}
+
+/// Returns the witness for the tip of the log, or `None` if the log is empty.
+pub fn tip_witness() -> Option<HashTree> {
+    let tip = TIP.with(|t| *t.borrow())?;
+    Some(root_tree(
+        pruned_balances(),
+        tip_trees(Some(tip)),
+        pruned_transactions(),
+    ))
+}
+
+/// Serializes a witness as self-describing CBOR, the encoding expected by IC agents.
+pub fn encode_witness(tree: &HashTree) -> Vec<u8> {
+    let mut serializer = serde_cbor::Serializer::new(Vec::new());
+    serializer
+        .self_describe()
+        .expect("Cannot write the CBOR self-describe tag");
+    tree.serialize(&mut serializer)
+        .expect("Cannot serialize the witness");
+    serializer.into_inner()
+}
@@ -0,0 +1,331 @@
+use crate::icrc1::{TOKEN_NAME, TOKEN_SYMBOL};
+use crate::money::Money;
+use crate::roles::{caller_is_admin, has_role, Role};
+use crate::{
+    _get_account, certification, do_insert_account, ensure_can_view, ensure_not_anonymous,
+    ensure_owner, lifecycle, overdraft, validation, Account, Error, Memory, TransferPayload,
+    MEMORY_MANAGER,
+};
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::{caller, data_certificate};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+/// Code of the base currency, the ledger's own token.
+///
+/// Holds, overdrafts, fees, escrow and the ICRC interfaces all work in the base currency. The
+/// code is not an ISO 4217 code but the ledger's own private unit, with `Money::DECIMALS`
+/// decimal places; it is reserved, so it can never be registered as another currency.
+pub const BASE_CURRENCY: &str = TOKEN_SYMBOL;
+
+/// Maximum number of currencies that can be registered besides the base currency, chosen so
+/// that an `Account` holding every one of them still fits within its `BoundedStorable::MAX_SIZE`.
+pub const MAX_CURRENCIES: u64 = 16;
+
+/// Maximum number of decimal places a currency may use.
+pub const MAX_DECIMALS: u8 = 8;
+
+/// Maximum length of a currency name in bytes.
+pub const MAX_CURRENCY_NAME_LEN: usize = 64;
+
+/// Represents a currency accounts may hold balances in.
+///
+/// `code` is the ISO 4217 code and `decimals` the number of decimal places between the minor
+/// unit, in which amounts are given, and the major unit.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct Currency {
+    pub code: String,
+    pub name: String,
+    pub decimals: u8,
+}
+
+impl Storable for Currency {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Currency {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Represents the explicit conversion of a transfer into another currency: the receiver is
+/// credited `amount` in `currency` instead of the amount sent.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct Conversion {
+    pub currency: String,
+    pub amount: Money,
+}
+
+/// Represents an account's balance in one currency.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct CurrencyBalance {
+    pub currency: String,
+    pub decimals: u8,
+    pub balance: Money,
+}
+
+/// Represents an account's balances in every currency it holds, together with the certificate
+/// and witness that prove them.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct CertifiedBalances {
+    pub balances: Vec<CurrencyBalance>,
+    pub certificate: Option<Vec<u8>>,
+    pub witness: Vec<u8>,
+}
+
+// Thread-local storage for the registered currencies, keyed by their code
+thread_local! {
+    static CURRENCIES: RefCell<StableBTreeMap<[u8; 3], Currency, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(26)))
+    ));
+}
+
+/// Turns a currency code into its stable map key, checking that it is three uppercase letters.
+pub fn code_key(code: &str) -> Result<[u8; 3], Error> {
+    code.as_bytes()
+        .try_into()
+        .ok()
+        .filter(|key: &[u8; 3]| key.iter().all(u8::is_ascii_uppercase))
+        .ok_or_else(|| Error::InvalidCurrency {
+            msg: format!("Currency code {:?} is not three uppercase letters.", code),
+        })
+}
+
+// Helper function to describe the base currency as a registry entry
+fn base() -> Currency {
+    Currency {
+        code: BASE_CURRENCY.to_string(),
+        name: TOKEN_NAME.to_string(),
+        decimals: Money::DECIMALS as u8,
+    }
+}
+
+/// Returns the supported currency with the given code, the base currency included.
+pub fn get(code: &str) -> Result<Currency, Error> {
+    if code == BASE_CURRENCY {
+        return Ok(base());
+    }
+    let key = code_key(code)?;
+    CURRENCIES
+        .with(|c| c.borrow().get(&key))
+        .ok_or_else(|| Error::InvalidCurrency {
+            msg: format!("Currency {} is not supported.", code),
+        })
+}
+
+/// Formats an amount in the given currency with that currency's decimal places and its code,
+/// e.g. `12.5000 CHF`.
+pub fn format(code: &str, amount: Money) -> String {
+    match get(code) {
+        Ok(currency) => format!("{} {}", amount.format_with(currency.decimals), code),
+        Err(_) => format!("{} {}", amount, code),
+    }
+}
+
+/// Checks that an optional currency code names a supported currency, mapping the base
+/// currency to none so that it is always recorded the same way.
+pub fn resolve(currency: &Option<String>) -> Result<Option<String>, Error> {
+    match currency {
+        Some(code) if code != BASE_CURRENCY => get(code).map(|currency| Some(currency.code)),
+        _ => Ok(None),
+    }
+}
+
+/// Returns the code of an optional currency, none standing for the base currency.
+pub fn code_of(currency: &Option<String>) -> &str {
+    currency.as_deref().unwrap_or(BASE_CURRENCY)
+}
+
+/// Checks the currency and conversion of a transfer made on behalf of the given principal,
+/// returning its currency as recorded on the transaction.
+///
+/// Conversions create money in the target currency at a rate agreed outside the ledger, so
+/// only tellers may make them.
+pub fn check_transfer(
+    owner: &Principal,
+    payload: &TransferPayload,
+) -> Result<Option<String>, Error> {
+    let currency = resolve(&payload.currency)?;
+    if let Some(conversion) = &payload.conversion {
+        if !has_role(owner, Role::Teller) {
+            return Err(Error::Unauthorized {
+                msg: "Only tellers may convert transfers between currencies.".to_string(),
+            });
+        }
+        validation::validate_amount(conversion.amount)?;
+        if get(&conversion.currency)?.code == code_of(&currency) {
+            return Err(Error::InvalidCurrency {
+                msg: format!(
+                    "Cannot convert a transfer into {}, the currency it is sent in.",
+                    conversion.currency
+                ),
+            });
+        }
+    }
+    Ok(currency)
+}
+
+/// Returns an account's balance in the given currency, or none if it does not hold that currency.
+pub fn balance_in(account: &Account, code: &str) -> Option<Money> {
+    if code == BASE_CURRENCY {
+        Some(account.balance)
+    } else {
+        account.foreign_balances.get(code).copied()
+    }
+}
+
+/// Returns how much an account can spend in the given currency: its available balance plus
+/// its overdraft in the base currency, and its balance in any other.
+pub fn spendable_in(account: &Account, code: &str) -> Result<Money, Error> {
+    if code == BASE_CURRENCY {
+        overdraft::spendable(account)
+    } else {
+        Ok(balance_in(account, code).unwrap_or(Money::ZERO))
+    }
+}
+
+/// Debits an amount in the given currency from an account.
+///
+/// The caller is expected to have checked `spendable_in` first.
+pub fn debit(account: &mut Account, code: &str, amount: Money) -> Result<(), Error> {
+    let balance = balance_in(account, code).ok_or_else(|| Error::CurrencyMismatch {
+        msg: format!("Account with id={} does not hold {}.", account.id, code),
+    })?;
+    set_balance(account, code, balance.checked_sub(amount)?);
+    Ok(())
+}
+
+/// Credits an amount in the given currency to an account, rejecting currencies it does not
+/// hold; such funds have to be converted explicitly into one it does.
+pub fn credit(account: &mut Account, code: &str, amount: Money) -> Result<(), Error> {
+    let balance = balance_in(account, code).ok_or_else(|| Error::CurrencyMismatch {
+        msg: format!(
+            "Account with id={} does not hold {}; the funds must be converted explicitly.",
+            account.id, code
+        ),
+    })?;
+    set_balance(account, code, balance.checked_add(amount)?);
+    Ok(())
+}
+
+// Helper function to overwrite an account's balance in the given currency
+fn set_balance(account: &mut Account, code: &str, balance: Money) {
+    if code == BASE_CURRENCY {
+        account.balance = balance;
+    } else {
+        account.foreign_balances.insert(code.to_string(), balance);
+    }
+}
+
+/// Adds a currency to the registry.
+///
+/// A currency cannot be changed or removed once registered, since balances may be held in it.
+#[ic_cdk::update(guard = "caller_is_admin")]
+fn register_currency(currency: Currency) -> Result<Currency, Error> {
+    let key = code_key(&currency.code)?;
+    if currency.code == BASE_CURRENCY || CURRENCIES.with(|c| c.borrow().contains_key(&key)) {
+        return Err(Error::InvalidCurrency {
+            msg: format!("Currency {} is already registered.", currency.code),
+        });
+    }
+    if currency.name.trim().is_empty() || currency.name.len() > MAX_CURRENCY_NAME_LEN {
+        return Err(Error::InvalidCurrency {
+            msg: format!(
+                "Currency name must be between 1 and {} bytes long.",
+                MAX_CURRENCY_NAME_LEN
+            ),
+        });
+    }
+    if currency.decimals > MAX_DECIMALS {
+        return Err(Error::InvalidCurrency {
+            msg: format!(
+                "Currency {} has {} decimals, the maximum is {}.",
+                currency.code, currency.decimals, MAX_DECIMALS
+            ),
+        });
+    }
+    if CURRENCIES.with(|c| c.borrow().len()) >= MAX_CURRENCIES {
+        return Err(Error::InvalidCurrency {
+            msg: format!(
+                "No more than {} currencies can be registered.",
+                MAX_CURRENCIES
+            ),
+        });
+    }
+
+    CURRENCIES.with(|c| c.borrow_mut().insert(key, currency.clone()));
+    Ok(currency)
+}
+
+/// Returns the registered currencies, without the base currency.
+pub fn registered() -> Vec<Currency> {
+    CURRENCIES.with(|c| c.borrow().iter().map(|(_, currency)| currency).collect())
+}
+
+/// Retrieves the supported currencies, starting with the base currency.
+#[ic_cdk::query]
+fn list_currencies() -> Vec<Currency> {
+    let mut currencies = vec![base()];
+    currencies.extend(registered());
+    currencies
+}
+
+/// Lets an account hold and receive a supported currency, starting from a zero balance.
+///
+/// Only the account's owner may open a balance in a new currency.
+#[ic_cdk::update]
+fn open_currency_balance(account_id: u64, currency: String) -> Result<Account, Error> {
+    ensure_not_anonymous(&caller())?;
+    let currency = get(&currency)?;
+    let mut account = _get_account(&account_id).ok_or_else(|| Error::NotFound {
+        msg: format!("Account with id={} not found.", account_id),
+    })?;
+    ensure_owner(&account)?;
+    lifecycle::ensure_can_receive(&account)?;
+    if balance_in(&account, &currency.code).is_some() {
+        return Err(Error::InvalidCurrency {
+            msg: format!(
+                "Account with id={} already holds {}.",
+                account_id, currency.code
+            ),
+        });
+    }
+
+    account.foreign_balances.insert(currency.code, Money::ZERO);
+    do_insert_account(&account);
+    Ok(account)
+}
+
+/// Retrieves an account's balances in every currency it holds, starting with the base currency,
+/// with the certificate and witness that prove them.
+#[ic_cdk::query]
+fn get_balances(account_id: u64) -> Result<CertifiedBalances, Error> {
+    let account = _get_account(&account_id).ok_or_else(|| Error::NotFound {
+        msg: format!("Account with id={} not found.", account_id),
+    })?;
+    ensure_can_view(&account)?;
+    let held = std::iter::once((BASE_CURRENCY.to_string(), account.balance))
+        .chain(account.foreign_balances.clone());
+    let balances = held
+        .map(|(code, balance)| {
+            Ok(CurrencyBalance {
+                decimals: get(&code)?.decimals,
+                currency: code,
+                balance,
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+    Ok(CertifiedBalances {
+        balances,
+        certificate: data_certificate(),
+        witness: certification::encode_witness(&certification::balance_witness(account_id)),
+    })
+}
@@ -0,0 +1,134 @@
+use crate::icrc1::{PERMITTED_DRIFT_NANOS, TRANSACTION_WINDOW_NANOS};
+use crate::icrc3::Hash;
+use crate::{Error, Memory, MEMORY_MANAGER};
+use candid::Principal;
+use ic_cdk::api::time;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::StableBTreeMap;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+/// Maximum number of expired entries removed from the deduplication window per call.
+pub const MAX_PRUNED_PER_CALL: usize = 100;
+
+// Thread-local storage for the deduplication window
+//
+// Entries are keyed by (created_at_time, hash of caller and request) so that expired
+// entries can be dropped in order of age; the value is the ID of the recorded transaction.
+thread_local! {
+    static RECENT_REQUESTS: RefCell<StableBTreeMap<(u64, Hash), u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10)))
+    ));
+}
+
+// Helper function to derive the key of a request from its caller and the bytes identifying it
+fn request_key(caller: &Principal, request: &[u8], created_at_time: u64) -> (u64, Hash) {
+    let mut hasher = Sha256::new();
+    hasher.update([caller.as_slice().len() as u8]);
+    hasher.update(caller.as_slice());
+    hasher.update(request);
+    (created_at_time, hasher.finalize().into())
+}
+
+/// Represents why a `created_at_time` lies outside the deduplication window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowError {
+    TooOld {
+        created_at_time: u64,
+    },
+    CreatedInFuture {
+        created_at_time: u64,
+        ledger_time: u64,
+    },
+}
+
+// Map window errors onto the errors of `transfer_funds`
+impl From<WindowError> for Error {
+    fn from(error: WindowError) -> Self {
+        let msg = match error {
+            WindowError::TooOld { created_at_time } => format!(
+                "Request created at {} is older than the deduplication window.",
+                created_at_time
+            ),
+            WindowError::CreatedInFuture {
+                created_at_time,
+                ledger_time,
+            } => format!(
+                "Request created at {} is in the future, ledger time is {}.",
+                created_at_time, ledger_time
+            ),
+        };
+        Error::InvalidCreatedAtTime { msg }
+    }
+}
+
+/// Checks that `created_at_time` lies within the deduplication window.
+///
+/// This is the only window check; each interface maps its error onto its own error type.
+pub fn check_created_at_time(created_at_time: u64) -> Result<(), WindowError> {
+    let now = time();
+    if created_at_time.saturating_add(TRANSACTION_WINDOW_NANOS + PERMITTED_DRIFT_NANOS) < now {
+        return Err(WindowError::TooOld { created_at_time });
+    }
+    if created_at_time > now.saturating_add(PERMITTED_DRIFT_NANOS) {
+        return Err(WindowError::CreatedInFuture {
+            created_at_time,
+            ledger_time: now,
+        });
+    }
+    Ok(())
+}
+
+/// Returns the ID of the transaction recorded for an identical earlier request by the caller.
+///
+/// `request` identifies the request: the tagged idempotency key of a `transfer_funds` call, or the
+/// method name and encoded argument of an ICRC call.
+pub fn find_duplicate(caller: &Principal, request: &[u8], created_at_time: u64) -> Option<u64> {
+    let key = request_key(caller, request, created_at_time);
+    RECENT_REQUESTS.with(|r| r.borrow().get(&key))
+}
+
+/// Identifies a `transfer_funds` request for deduplication by its idempotency key.
+///
+/// The key is prefixed with the method name, as ICRC requests are, so that a key can never
+/// collide with the bytes of an ICRC request.
+pub fn idempotency_bytes(idempotency_key: &str) -> Vec<u8> {
+    let mut bytes = b"transfer_funds".to_vec();
+    bytes.extend(idempotency_key.as_bytes());
+    bytes
+}
+
+/// Returns a `Duplicate` error if the caller already made a request with the same key.
+pub fn check_duplicate(
+    caller: &Principal,
+    idempotency_key: &str,
+    created_at_time: u64,
+) -> Result<(), Error> {
+    match find_duplicate(caller, &idempotency_bytes(idempotency_key), created_at_time) {
+        Some(existing_tx_id) => Err(Error::Duplicate { existing_tx_id }),
+        None => Ok(()),
+    }
+}
+
+/// Remembers the transaction recorded for a request so that retries are rejected.
+pub fn remember(caller: &Principal, request: &[u8], created_at_time: u64, transaction_id: u64) {
+    let key = request_key(caller, request, created_at_time);
+    RECENT_REQUESTS.with(|r| r.borrow_mut().insert(key, transaction_id));
+}
+
+/// Drops the oldest entries that have fallen out of the deduplication window.
+pub fn prune_expired() {
+    let cutoff = time().saturating_sub(TRANSACTION_WINDOW_NANOS + PERMITTED_DRIFT_NANOS);
+    RECENT_REQUESTS.with(|r| {
+        let mut r = r.borrow_mut();
+        let expired: Vec<(u64, Hash)> = r
+            .range(..(cutoff, [0; 32]))
+            .take(MAX_PRUNED_PER_CALL)
+            .map(|(key, _)| key)
+            .collect();
+        for key in expired {
+            r.remove(&key);
+        }
+    });
+}
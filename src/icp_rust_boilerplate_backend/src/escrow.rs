@@ -0,0 +1,452 @@
+use crate::money::Money;
+use crate::roles::is_staff;
+use crate::timers::{self, Queue, TimerSlot};
+use crate::{
+    _get_account, do_insert_account, ensure_owner, get_account_or_not_found, lifecycle, overdraft,
+    record_transaction, validation, Error, IdCell, Memory, Transaction, TransactionKind,
+    MEMORY_MANAGER,
+};
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::{caller, time};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+/// Maximum number of expired escrows refunded by a single timer callback.
+pub const MAX_REFUNDS_PER_TICK: usize = 50;
+
+/// Delay before a failed automatic refund is attempted again, in nanoseconds.
+pub const REFUND_RETRY_DELAY_NANOS: u64 = 10 * 60 * 1_000_000_000;
+
+/// Represents the state of an escrow.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum EscrowStatus {
+    /// The funds are held until the escrow is released or refunded.
+    Held,
+    /// The funds were paid out to the payee.
+    Released,
+    /// The funds were returned to the payer.
+    Refunded,
+}
+
+/// Represents funds taken from a payer's account and held until they are paid to the payee
+/// or returned to the payer.
+///
+/// The payer or the arbiter may release the funds to the payee; the payee or the arbiter may
+/// refund them to the payer. Funds still held at the deadline are refunded automatically.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct Escrow {
+    pub id: u64,
+    pub payer_id: u64,
+    pub payee_id: u64,
+    pub amount: Money,
+    pub arbiter: Principal,
+    pub deadline: u64,
+    pub status: EscrowStatus,
+    pub hold_transaction_id: u64,
+    pub settlement_transaction_id: Option<u64>,
+    pub created_at: u64,
+}
+
+impl Storable for Escrow {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Escrow {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Represents the latest failed attempt to refund an escrow automatically after its deadline.
+///
+/// The refund is attempted again at `next_attempt_at` until it succeeds or the escrow is
+/// settled by hand.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct RefundFailure {
+    pub attempted_at: u64,
+    pub attempt: u32,
+    pub next_attempt_at: u64,
+    pub error: Error,
+}
+
+impl Storable for RefundFailure {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for RefundFailure {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Thread-local storage for escrows
+//
+// Held escrows are also keyed by (deadline, escrow_id) so that the next one to expire is
+// always the first key; an escrow whose refund failed is keyed by its next attempt instead.
+// Held escrows are indexed by (account_id, escrow_id) under both their payer and their payee.
+thread_local! {
+    static ESCROWS: RefCell<StableBTreeMap<u64, Escrow, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18)))
+    ));
+
+    static ESCROW_DEADLINES: RefCell<Queue> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19)))
+    ));
+
+    static ESCROW_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(20))), 0)
+            .expect("Cannot create an escrow ID counter")
+    );
+
+    static REFUND_FAILURES: RefCell<StableBTreeMap<u64, RefundFailure, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(28)))
+    ));
+
+    static HELD_ESCROWS: RefCell<StableBTreeMap<(u64, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(30)))
+    ));
+
+    static TIMER: TimerSlot = const { RefCell::new(None) };
+}
+
+/// Sets the timer for the earliest escrow deadline, replacing any timer already set.
+pub fn rearm() {
+    timers::rearm(&TIMER, &ESCROW_DEADLINES, refund_expired_escrows);
+}
+
+// Helper function to set the timer for the next deadline, then refund every held escrow past
+// its deadline
+//
+// An escrow that cannot be refunded, e.g. because the payer's account is frozen, stays held
+// and can still be settled by hand; the failure is recorded and the refund attempted again
+// after `REFUND_RETRY_DELAY_NANOS`.
+fn refund_expired_escrows() {
+    let now = time();
+    let expired = timers::take_due(
+        &TIMER,
+        &ESCROW_DEADLINES,
+        now,
+        MAX_REFUNDS_PER_TICK,
+        refund_expired_escrows,
+    );
+    for key in expired {
+        let escrow = match ESCROWS.with(|e| e.borrow().get(&key.1)) {
+            Some(escrow) if escrow.status == EscrowStatus::Held => escrow,
+            _ => {
+                ESCROW_DEADLINES.with(|d| d.borrow_mut().remove(&key));
+                continue;
+            }
+        };
+        if let Err(error) = settle(escrow, EscrowStatus::Refunded) {
+            let attempt = REFUND_FAILURES
+                .with(|f| f.borrow().get(&key.1))
+                .map_or(1, |failure| failure.attempt.saturating_add(1));
+            let next_attempt_at = now.saturating_add(REFUND_RETRY_DELAY_NANOS);
+            ESCROW_DEADLINES.with(|d| {
+                let mut d = d.borrow_mut();
+                d.remove(&key);
+                d.insert((next_attempt_at, key.1), ());
+            });
+            let failure = RefundFailure {
+                attempted_at: now,
+                attempt,
+                next_attempt_at,
+                error,
+            };
+            REFUND_FAILURES.with(|f| f.borrow_mut().insert(key.1, failure));
+        }
+    }
+    rearm();
+}
+
+/// Returns true if the account is the payer or the payee of an escrow whose funds are still held.
+pub fn has_held_escrows(account_id: u64) -> bool {
+    HELD_ESCROWS.with(|index| {
+        index
+            .borrow()
+            .range((account_id, 0)..=(account_id, u64::MAX))
+            .next()
+            .is_some()
+    })
+}
+
+// Helper function to add a held escrow to, or drop it from, the index of its parties
+fn index_held(escrow: &Escrow, held: bool) {
+    HELD_ESCROWS.with(|index| {
+        let mut index = index.borrow_mut();
+        for account_id in [escrow.payer_id, escrow.payee_id] {
+            if held {
+                index.insert((account_id, escrow.id), ());
+            } else {
+                index.remove(&(account_id, escrow.id));
+            }
+        }
+    });
+}
+
+/// Indexes every held escrow under its payer and its payee.
+///
+/// Escrows created before the index was introduced are indexed once, when the canister is
+/// upgraded.
+pub fn index_held_escrows() {
+    let held: Vec<Escrow> = ESCROWS.with(|e| {
+        e.borrow()
+            .iter()
+            .map(|(_, escrow)| escrow)
+            .filter(|escrow| escrow.status == EscrowStatus::Held)
+            .collect()
+    });
+    for escrow in &held {
+        index_held(escrow, true);
+    }
+}
+
+// Helper function to retrieve an escrow whose funds are still held
+fn get_held_escrow(id: u64) -> Result<Escrow, Error> {
+    let escrow = ESCROWS
+        .with(|e| e.borrow().get(&id))
+        .ok_or_else(|| Error::NotFound {
+            msg: format!("Escrow with id={} not found.", id),
+        })?;
+    if escrow.status != EscrowStatus::Held {
+        return Err(Error::InvalidEscrow {
+            msg: format!("Escrow with id={} has already been settled.", id),
+        });
+    }
+    Ok(escrow)
+}
+
+// Helper function to reject callers other than the given account's owner or the arbiter
+fn ensure_owner_or_arbiter(escrow: &Escrow, account_id: u64) -> Result<(), Error> {
+    let caller = caller();
+    let account = get_account_or_not_found(account_id)?;
+    if caller == escrow.arbiter || caller == account.owner {
+        Ok(())
+    } else {
+        Err(Error::Unauthorized {
+            msg: format!("Caller may not settle the escrow with id={}.", escrow.id),
+        })
+    }
+}
+
+// Helper function to pay out a held escrow to the payee or back to the payer
+fn settle(mut escrow: Escrow, outcome: EscrowStatus) -> Result<Escrow, Error> {
+    let (kind, account_id) = match outcome {
+        EscrowStatus::Released => (TransactionKind::EscrowRelease, escrow.payee_id),
+        _ => (TransactionKind::EscrowRefund, escrow.payer_id),
+    };
+    let mut account = get_account_or_not_found(account_id)?;
+    lifecycle::ensure_can_receive(&account)?;
+    account.balance = account.balance.checked_add(escrow.amount)?;
+
+    let transaction = record_transaction(Transaction {
+        kind,
+        receiver_id: Some(account_id),
+        amount: escrow.amount,
+        reference: Some(format!("Escrow id={}", escrow.id)),
+        ..Default::default()
+    })?;
+    do_insert_account(&account);
+    ESCROW_DEADLINES.with(|d| d.borrow_mut().remove(&(escrow.deadline, escrow.id)));
+    if let Some(failure) = REFUND_FAILURES.with(|f| f.borrow_mut().remove(&escrow.id)) {
+        ESCROW_DEADLINES.with(|d| d.borrow_mut().remove(&(failure.next_attempt_at, escrow.id)));
+    }
+    escrow.status = outcome;
+    escrow.settlement_transaction_id = Some(transaction.id);
+    ESCROWS.with(|e| e.borrow_mut().insert(escrow.id, escrow.clone()));
+    index_held(&escrow, false);
+    Ok(escrow)
+}
+
+/// Moves funds out of the payer's account into escrow until they are released to the payee,
+/// refunded to the payer, or the deadline passes and they are refunded automatically.
+#[ic_cdk::update]
+fn create_escrow(
+    payer_id: u64,
+    payee_id: u64,
+    amount: Money,
+    arbiter: Principal,
+    deadline: u64,
+) -> Result<Escrow, Error> {
+    // Validate the request before any state is touched
+    validation::validate_amount(amount)?;
+    if payer_id == payee_id {
+        return Err(Error::SelfTransfer {
+            msg: format!(
+                "Cannot escrow funds from account with id={} to itself.",
+                payer_id
+            ),
+        });
+    }
+    let now = time();
+    if deadline <= now {
+        return Err(Error::InvalidEscrow {
+            msg: format!("Deadline {} is not in the future.", deadline),
+        });
+    }
+
+    let mut payer = get_account_or_not_found(payer_id)?;
+    let payee = get_account_or_not_found(payee_id)?;
+    ensure_owner(&payer)?;
+    lifecycle::ensure_can_send(&payer)?;
+    lifecycle::ensure_can_receive(&payee)?;
+    if overdraft::spendable(&payer)? < amount {
+        return Err(Error::InsufficientFunds {
+            msg: format!("Insufficient funds in account with id={}.", payer_id),
+        });
+    }
+    payer.balance = payer.balance.checked_sub(amount)?;
+
+    let id = ESCROW_ID_COUNTER
+        .with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter.borrow_mut().set(current_value + 1)
+        })
+        .expect("Cannot increment escrow ID counter");
+    let hold = record_transaction(Transaction {
+        kind: TransactionKind::EscrowHold,
+        sender_id: Some(payer_id),
+        amount,
+        reference: Some(format!("Escrow id={}", id)),
+        ..Default::default()
+    })?;
+    do_insert_account(&payer);
+
+    let escrow = Escrow {
+        id,
+        payer_id,
+        payee_id,
+        amount,
+        arbiter,
+        deadline,
+        status: EscrowStatus::Held,
+        hold_transaction_id: hold.id,
+        settlement_transaction_id: None,
+        created_at: now,
+    };
+    ESCROWS.with(|e| e.borrow_mut().insert(id, escrow.clone()));
+    ESCROW_DEADLINES.with(|d| d.borrow_mut().insert((deadline, id), ()));
+    index_held(&escrow, true);
+    rearm();
+    Ok(escrow)
+}
+
+/// Pays the escrowed funds out to the payee; only the payer or the arbiter may do so.
+#[ic_cdk::update]
+fn release_escrow(id: u64) -> Result<Escrow, Error> {
+    let escrow = get_held_escrow(id)?;
+    ensure_owner_or_arbiter(&escrow, escrow.payer_id)?;
+    let escrow = settle(escrow, EscrowStatus::Released)?;
+    rearm();
+    Ok(escrow)
+}
+
+/// Returns the escrowed funds to the payer; only the payee or the arbiter may do so.
+#[ic_cdk::update]
+fn refund_escrow(id: u64) -> Result<Escrow, Error> {
+    let escrow = get_held_escrow(id)?;
+    ensure_owner_or_arbiter(&escrow, escrow.payee_id)?;
+    let escrow = settle(escrow, EscrowStatus::Refunded)?;
+    rearm();
+    Ok(escrow)
+}
+
+// Helper function to check whether the caller is a party to the escrow
+fn is_party(escrow: &Escrow, caller: &Principal) -> bool {
+    *caller == escrow.arbiter
+        || [escrow.payer_id, escrow.payee_id]
+            .iter()
+            .filter_map(_get_account)
+            .any(|account| account.owner == *caller)
+}
+
+// Helper function to retrieve an escrow the caller is allowed to see
+fn get_visible_escrow(id: u64) -> Result<Escrow, Error> {
+    let escrow = ESCROWS
+        .with(|e| e.borrow().get(&id))
+        .ok_or_else(|| Error::NotFound {
+            msg: format!("Escrow with id={} not found.", id),
+        })?;
+    let caller = caller();
+    if !is_party(&escrow, &caller) && !is_staff(&caller) {
+        return Err(Error::Unauthorized {
+            msg: format!("Caller may not view the escrow with id={}.", id),
+        });
+    }
+    Ok(escrow)
+}
+
+/// Retrieves an escrow; only its parties and staff may see it.
+#[ic_cdk::query]
+fn get_escrow(id: u64) -> Result<Escrow, Error> {
+    get_visible_escrow(id)
+}
+
+/// Retrieves the latest failed attempt to refund an escrow after its deadline, if any.
+#[ic_cdk::query]
+fn get_escrow_refund_failure(id: u64) -> Result<Option<RefundFailure>, Error> {
+    let escrow = get_visible_escrow(id)?;
+    Ok(REFUND_FAILURES.with(|f| f.borrow().get(&escrow.id)))
+}
+
+/// Retrieves the escrows the caller is a party to, or all of them for staff.
+#[ic_cdk::query]
+fn list_escrows() -> Vec<Escrow> {
+    let caller = caller();
+    let staff = is_staff(&caller);
+    ESCROWS.with(|e| {
+        e.borrow()
+            .iter()
+            .map(|(_, escrow)| escrow)
+            .filter(|escrow| staff || is_party(escrow, &caller))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escrow(id: u64, payer_id: u64, payee_id: u64) -> Escrow {
+        Escrow {
+            id,
+            payer_id,
+            payee_id,
+            amount: Money::from_minor_units(500),
+            arbiter: Principal::management_canister(),
+            deadline: 0,
+            status: EscrowStatus::Held,
+            hold_transaction_id: 0,
+            settlement_transaction_id: None,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn held_escrows_are_found_for_both_parties_until_settled() {
+        let escrow = escrow(7, 1, 2);
+        index_held(&escrow, true);
+        assert!(has_held_escrows(1));
+        assert!(has_held_escrows(2));
+        assert!(!has_held_escrows(3));
+
+        index_held(&escrow, false);
+        assert!(!has_held_escrows(1));
+        assert!(!has_held_escrows(2));
+    }
+}
@@ -0,0 +1,381 @@
+use crate::currency;
+use crate::money::Money;
+use crate::roles::caller_is_admin;
+use crate::{
+    _get_account, lifecycle, validation, Account, Error, Memory, TransferPayload, MEMORY_MANAGER,
+};
+use candid::{Decode, Encode};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{Cell, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+/// Number of basis points in one whole, i.e. a fee of 10 000 basis points is 100%.
+pub const BASIS_POINTS_PER_UNIT: u32 = 10_000;
+
+/// Maximum number of tiers in a tiered fee rule.
+pub const MAX_FEE_TIERS: usize = 16;
+
+/// Represents one band of a tiered fee rule.
+///
+/// The band applies to transfers of at least `min_amount`, up to the `min_amount` of the next tier.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct FeeTier {
+    pub min_amount: Money,
+    pub flat: Money,
+    pub basis_points: u32,
+}
+
+/// Represents how the fee of a transfer is derived from its amount.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub enum FeeRule {
+    /// The same fee is charged on every transfer.
+    Flat(Money),
+    /// A share of the amount is charged, in basis points.
+    Percentage { basis_points: u32 },
+    /// A flat fee plus a share of the amount, depending on the band the amount falls in.
+    Tiered(Vec<FeeTier>),
+}
+
+/// Represents the fee schedule applied to `transfer_funds`.
+///
+/// The fee computed by the rule is raised to `minimum` and, if set, lowered to `maximum`.
+/// Fees are credited to the treasury account, which must be set for a schedule that charges any.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct FeeSchedule {
+    pub rule: FeeRule,
+    pub minimum: Money,
+    pub maximum: Option<Money>,
+    pub treasury_account_id: Option<u64>,
+}
+
+// Default to charging no fees
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        Self {
+            rule: FeeRule::Flat(Money::ZERO),
+            minimum: Money::ZERO,
+            maximum: None,
+            treasury_account_id: None,
+        }
+    }
+}
+
+impl Storable for FeeSchedule {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+/// Represents the preview of a transfer: the amount received, the fee and the total debited.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct TransferQuote {
+    pub amount: Money,
+    pub fee: Money,
+    pub total: Money,
+    pub treasury_account_id: Option<u64>,
+}
+
+// Thread-local storage for the fee schedule
+thread_local! {
+    static FEE_SCHEDULE: RefCell<Cell<FeeSchedule, Memory>> = RefCell::new(
+        Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13))), FeeSchedule::default())
+            .expect("Cannot create the fee schedule cell")
+    );
+}
+
+// Helper function to compute a share of an amount in basis points, rounding up to the next minor unit
+fn percentage_of(amount: Money, basis_points: u32) -> Result<Money, Error> {
+    let units = i128::from(amount.minor_units()) * i128::from(basis_points);
+    let divisor = i128::from(BASIS_POINTS_PER_UNIT);
+    let share = (units + divisor - 1).div_euclid(divisor);
+    i64::try_from(share)
+        .map(Money::from_minor_units)
+        .map_err(|_| Error::ArithmeticOverflow {
+            msg: format!(
+                "Overflow while computing {} basis points of {}.",
+                basis_points, amount
+            ),
+        })
+}
+
+impl FeeSchedule {
+    /// Computes the fee charged on a transfer of the given amount.
+    pub fn fee_for(&self, amount: Money) -> Result<Money, Error> {
+        let fee = match &self.rule {
+            FeeRule::Flat(fee) => *fee,
+            FeeRule::Percentage { basis_points } => percentage_of(amount, *basis_points)?,
+            FeeRule::Tiered(tiers) => match tiers.iter().rev().find(|t| t.min_amount <= amount) {
+                Some(tier) => tier
+                    .flat
+                    .checked_add(percentage_of(amount, tier.basis_points)?)?,
+                None => Money::ZERO,
+            },
+        };
+        let fee = fee.max(self.minimum);
+        Ok(match self.maximum {
+            Some(maximum) => fee.min(maximum),
+            None => fee,
+        })
+    }
+
+    // Tells whether the schedule can charge a fee on any transfer
+    fn charges_fees(&self) -> bool {
+        let rule_charges = match &self.rule {
+            FeeRule::Flat(fee) => *fee > Money::ZERO,
+            FeeRule::Percentage { basis_points } => *basis_points > 0,
+            FeeRule::Tiered(tiers) => tiers
+                .iter()
+                .any(|tier| tier.flat > Money::ZERO || tier.basis_points > 0),
+        };
+        rule_charges || self.minimum > Money::ZERO
+    }
+
+    // Checks that every amount and rate is in range and that the treasury account can take fees
+    fn validate(&self) -> Result<(), Error> {
+        let invalid = |msg: String| Err(Error::InvalidFeeSchedule { msg });
+        let check_rate = |basis_points: u32| {
+            if basis_points > BASIS_POINTS_PER_UNIT {
+                invalid(format!(
+                    "Rate of {} basis points exceeds the maximum of {}.",
+                    basis_points, BASIS_POINTS_PER_UNIT
+                ))
+            } else {
+                Ok(())
+            }
+        };
+        let check_amount = |what: &str, amount: Money| {
+            if amount < Money::ZERO {
+                invalid(format!("{} must not be negative, got {}.", what, amount))
+            } else {
+                Ok(())
+            }
+        };
+
+        match &self.rule {
+            FeeRule::Flat(fee) => check_amount("Flat fee", *fee)?,
+            FeeRule::Percentage { basis_points } => check_rate(*basis_points)?,
+            FeeRule::Tiered(tiers) => {
+                if tiers.is_empty() || tiers.len() > MAX_FEE_TIERS {
+                    return invalid(format!(
+                        "Tiered fee rule must have between 1 and {} tiers, got {}.",
+                        MAX_FEE_TIERS,
+                        tiers.len()
+                    ));
+                }
+                for tier in tiers {
+                    check_amount("Tier minimum amount", tier.min_amount)?;
+                    check_amount("Tier flat fee", tier.flat)?;
+                    check_rate(tier.basis_points)?;
+                }
+                if tiers
+                    .windows(2)
+                    .any(|pair| pair[0].min_amount >= pair[1].min_amount)
+                {
+                    return invalid(
+                        "Tiers must be ordered by strictly increasing minimum amount.".to_string(),
+                    );
+                }
+            }
+        }
+
+        check_amount("Minimum fee", self.minimum)?;
+        if let Some(maximum) = self.maximum {
+            check_amount("Maximum fee", maximum)?;
+            if maximum < self.minimum {
+                return invalid(format!(
+                    "Maximum fee {} is below the minimum fee {}.",
+                    maximum, self.minimum
+                ));
+            }
+        }
+
+        match self.treasury_account_id {
+            Some(id) => match _get_account(&id) {
+                Some(treasury) => lifecycle::ensure_can_receive(&treasury)?,
+                None => return invalid(format!("Treasury account with id={} not found.", id)),
+            },
+            None if self.charges_fees() => {
+                return invalid(
+                    "A treasury account must be set to collect the fees of this schedule."
+                        .to_string(),
+                );
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+/// Returns the current fee schedule.
+pub fn schedule() -> FeeSchedule {
+    FEE_SCHEDULE.with(|s| s.borrow().get().clone())
+}
+
+/// Computes the fee charged on a transfer of the given amount under the current schedule.
+///
+/// A fee is only quoted if the treasury account can still collect it.
+pub fn quote(amount: Money) -> Result<TransferQuote, Error> {
+    let schedule = schedule();
+    let fee = schedule.fee_for(amount)?;
+    if fee > Money::ZERO {
+        treasury_account()?;
+    }
+    Ok(TransferQuote {
+        amount,
+        fee,
+        total: amount.checked_add(fee)?,
+        treasury_account_id: schedule.treasury_account_id,
+    })
+}
+
+/// Computes the fee charged on a transfer in the given currency; fees are only charged on
+/// transfers in the base currency.
+pub fn quote_in(currency: &Option<String>, amount: Money) -> Result<TransferQuote, Error> {
+    match currency {
+        None => quote(amount),
+        Some(_) => Ok(TransferQuote {
+            amount,
+            fee: Money::ZERO,
+            total: amount,
+            treasury_account_id: None,
+        }),
+    }
+}
+
+/// Returns the treasury account that collects fees.
+///
+/// The treasury's status may have changed since the schedule was set, so it is checked again
+/// every time and a treasury that can no longer receive funds is reported as such.
+pub fn treasury_account() -> Result<Account, Error> {
+    let id = schedule()
+        .treasury_account_id
+        .ok_or_else(|| Error::InvalidFeeSchedule {
+            msg: "No treasury account is configured.".to_string(),
+        })?;
+    let treasury = _get_account(&id).ok_or_else(|| Error::NotFound {
+        msg: format!("Treasury account with id={} not found.", id),
+    })?;
+    if lifecycle::ensure_can_receive(&treasury).is_err() {
+        return Err(Error::InvalidFeeSchedule {
+            msg: format!(
+                "Treasury account with id={} is {:?} and cannot collect fees.",
+                id, treasury.status
+            ),
+        });
+    }
+    Ok(treasury)
+}
+
+/// Replaces the fee schedule applied to transfers.
+#[ic_cdk::update(guard = "caller_is_admin")]
+fn set_fee_schedule(schedule: FeeSchedule) -> Result<(), Error> {
+    schedule.validate()?;
+    FEE_SCHEDULE
+        .with(|s| s.borrow_mut().set(schedule))
+        .expect("Cannot store the fee schedule");
+    Ok(())
+}
+
+/// Retrieves the fee schedule applied to transfers.
+///
+/// The schedule does not apply to the ICRC-1 and ICRC-2 interfaces, whose transfers are always
+/// free as reported by `icrc1_fee`.
+#[ic_cdk::query]
+fn get_fee_schedule() -> FeeSchedule {
+    schedule()
+}
+
+/// Previews the fee that `transfer_funds` would charge for the given payload.
+///
+/// The same transfer made through `icrc1_transfer` or `icrc2_transfer_from` is free.
+#[ic_cdk::query]
+fn quote_transfer(payload: TransferPayload) -> Result<TransferQuote, Error> {
+    validation::validate_transfer(&payload)?;
+    quote_in(&currency::resolve(&payload.currency)?, payload.amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(units: i64) -> Money {
+        Money::from_minor_units(units)
+    }
+
+    fn schedule(rule: FeeRule, minimum: i64, maximum: Option<i64>) -> FeeSchedule {
+        FeeSchedule {
+            rule,
+            minimum: money(minimum),
+            maximum: maximum.map(money),
+            treasury_account_id: Some(0),
+        }
+    }
+
+    fn tier(min_amount: i64, flat: i64, basis_points: u32) -> FeeTier {
+        FeeTier {
+            min_amount: money(min_amount),
+            flat: money(flat),
+            basis_points,
+        }
+    }
+
+    #[test]
+    fn percentage_rounds_up_to_the_next_minor_unit() {
+        assert_eq!(percentage_of(money(0), 1).unwrap(), money(0));
+        assert_eq!(percentage_of(money(1), 1).unwrap(), money(1));
+        assert_eq!(percentage_of(money(9_999), 1).unwrap(), money(1));
+        assert_eq!(percentage_of(money(10_000), 1).unwrap(), money(1));
+        assert_eq!(percentage_of(money(10_001), 1).unwrap(), money(2));
+        assert_eq!(percentage_of(money(200), 50).unwrap(), money(1));
+        assert_eq!(percentage_of(money(201), 50).unwrap(), money(2));
+        assert_eq!(
+            percentage_of(money(12_345), BASIS_POINTS_PER_UNIT).unwrap(),
+            money(12_345)
+        );
+    }
+
+    #[test]
+    fn percentage_reports_overflow() {
+        assert!(percentage_of(money(i64::MAX), BASIS_POINTS_PER_UNIT + 1).is_err());
+    }
+
+    #[test]
+    fn tier_applies_from_its_exact_minimum_amount() {
+        let schedule = schedule(
+            FeeRule::Tiered(vec![tier(100, 5, 0), tier(1_000, 10, 100)]),
+            0,
+            None,
+        );
+        assert_eq!(schedule.fee_for(money(99)).unwrap(), money(0));
+        assert_eq!(schedule.fee_for(money(100)).unwrap(), money(5));
+        assert_eq!(schedule.fee_for(money(999)).unwrap(), money(5));
+        assert_eq!(schedule.fee_for(money(1_000)).unwrap(), money(20));
+        assert_eq!(schedule.fee_for(money(1_001)).unwrap(), money(21));
+    }
+
+    #[test]
+    fn fee_is_raised_to_the_minimum_and_lowered_to_the_maximum() {
+        let schedule = schedule(FeeRule::Percentage { basis_points: 100 }, 5, Some(20));
+        assert_eq!(schedule.fee_for(money(100)).unwrap(), money(5));
+        assert_eq!(schedule.fee_for(money(500)).unwrap(), money(5));
+        assert_eq!(schedule.fee_for(money(1_000)).unwrap(), money(10));
+        assert_eq!(schedule.fee_for(money(2_000)).unwrap(), money(20));
+        assert_eq!(schedule.fee_for(money(10_000)).unwrap(), money(20));
+    }
+
+    #[test]
+    fn minimum_applies_below_the_first_tier() {
+        let schedule = schedule(FeeRule::Tiered(vec![tier(100, 5, 0)]), 2, None);
+        assert_eq!(schedule.fee_for(money(50)).unwrap(), money(2));
+    }
+
+    #[test]
+    fn flat_fee_is_lowered_to_the_maximum() {
+        let schedule = schedule(FeeRule::Flat(money(50)), 0, Some(30));
+        assert_eq!(schedule.fee_for(money(1)).unwrap(), money(30));
+    }
+}
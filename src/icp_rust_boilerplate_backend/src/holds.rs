@@ -0,0 +1,324 @@
+use crate::money::Money;
+use crate::roles::{has_role, Role};
+use crate::timers::{self, Queue, TimerSlot};
+use crate::{
+    _get_account, do_insert_account, do_transfer, ensure_can_view, fees, get_account_or_not_found,
+    lifecycle, overdraft, validation, Account, Error, IdCell, Memory, Transaction, MEMORY_MANAGER,
+};
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::{caller, time};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+/// Maximum number of expired holds released by a single timer callback.
+pub const MAX_EXPIRIES_PER_TICK: usize = 50;
+
+/// Represents the state of an authorization hold.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum HoldStatus {
+    /// The amount is reserved on the account.
+    Active,
+    /// The hold was turned into a transfer.
+    Captured,
+    /// The hold was lifted without moving any funds.
+    Released,
+    /// The hold lapsed at its expiry without being captured.
+    Expired,
+}
+
+/// Represents an amount reserved on an account, which lowers its available balance without
+/// moving any funds until the hold is captured.
+///
+/// The principal that placed the hold, or a teller, may capture it; they or the account's
+/// owner may release it. Holds still active at their expiry are released automatically.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct Hold {
+    pub id: u64,
+    pub account_id: u64,
+    pub amount: Money,
+    pub expires_at: u64,
+    pub status: HoldStatus,
+    pub placed_by: Principal,
+    pub capture_transaction_id: Option<u64>,
+    pub created_at: u64,
+}
+
+impl Storable for Hold {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Hold {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Thread-local storage for authorization holds
+//
+// Active holds are also keyed by (expires_at, hold_id) so that the next one to lapse is
+// always the first key. Every hold is also indexed by (account_id, hold_id).
+thread_local! {
+    static HOLDS: RefCell<StableBTreeMap<u64, Hold, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(21)))
+    ));
+
+    static HOLD_EXPIRIES: RefCell<Queue> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(22)))
+    ));
+
+    static HOLD_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(23))), 0)
+            .expect("Cannot create a hold ID counter")
+    );
+
+    static ACCOUNT_HOLDS: RefCell<StableBTreeMap<(u64, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(31)))
+    ));
+
+    static TIMER: TimerSlot = const { RefCell::new(None) };
+}
+
+/// Sets the timer for the earliest hold expiry, replacing any timer already set.
+pub fn rearm() {
+    timers::rearm(&TIMER, &HOLD_EXPIRIES, release_expired_holds);
+}
+
+// Helper function to set the timer for the next expiry, then release every active hold past
+// its expiry
+fn release_expired_holds() {
+    let expired = timers::take_due(
+        &TIMER,
+        &HOLD_EXPIRIES,
+        time(),
+        MAX_EXPIRIES_PER_TICK,
+        release_expired_holds,
+    );
+    for (_, id) in expired {
+        if let Some(hold) = HOLDS.with(|h| h.borrow().get(&id)) {
+            end_hold(hold, HoldStatus::Expired, None);
+        }
+    }
+    rearm();
+}
+
+/// Returns the total amount reserved on an account by its active holds.
+pub fn active_total(account_id: u64) -> Result<Money, Error> {
+    holds_on(account_id)
+        .iter()
+        .filter(|hold| hold.status == HoldStatus::Active)
+        .try_fold(Money::ZERO, |total, hold| total.checked_add(hold.amount))
+}
+
+// Helper function to retrieve the holds placed on an account through the index
+fn holds_on(account_id: u64) -> Vec<Hold> {
+    let ids: Vec<u64> = ACCOUNT_HOLDS.with(|index| {
+        index
+            .borrow()
+            .range((account_id, 0)..=(account_id, u64::MAX))
+            .map(|((_, id), _)| id)
+            .collect()
+    });
+    HOLDS.with(|h| {
+        let h = h.borrow();
+        ids.into_iter().filter_map(|id| h.get(&id)).collect()
+    })
+}
+
+/// Indexes every hold under the account it is placed on.
+///
+/// Holds placed before the index was introduced are indexed once, when the canister is
+/// upgraded.
+pub fn index_holds() {
+    let keys: Vec<(u64, u64)> = HOLDS.with(|h| {
+        h.borrow()
+            .iter()
+            .map(|(id, hold)| (hold.account_id, id))
+            .collect()
+    });
+    ACCOUNT_HOLDS.with(|index| {
+        let mut index = index.borrow_mut();
+        for key in keys {
+            index.insert(key, ());
+        }
+    });
+}
+
+// Helper function to retrieve a hold that is still active
+fn get_active_hold(id: u64) -> Result<Hold, Error> {
+    let hold = HOLDS
+        .with(|h| h.borrow().get(&id))
+        .ok_or_else(|| Error::NotFound {
+            msg: format!("Hold with id={} not found.", id),
+        })?;
+    if hold.status != HoldStatus::Active {
+        return Err(Error::InvalidHold {
+            msg: format!("Hold with id={} is no longer active.", id),
+        });
+    }
+    Ok(hold)
+}
+
+// Helper function to return an account with a hold's amount no longer reserved
+fn without_hold(mut account: Account, hold: &Hold) -> Result<Account, Error> {
+    account.held = account.held.checked_sub(hold.amount)?;
+    Ok(account)
+}
+
+// Helper function to record the end of a hold, lifting its reservation unless already done
+fn end_hold(mut hold: Hold, status: HoldStatus, capture_transaction_id: Option<u64>) -> Hold {
+    if capture_transaction_id.is_none() {
+        if let Some(account) = _get_account(&hold.account_id) {
+            if let Ok(account) = without_hold(account, &hold) {
+                do_insert_account(&account);
+            }
+        }
+    }
+    HOLD_EXPIRIES.with(|h| h.borrow_mut().remove(&(hold.expires_at, hold.id)));
+    hold.status = status;
+    hold.capture_transaction_id = capture_transaction_id;
+    HOLDS.with(|h| h.borrow_mut().insert(hold.id, hold.clone()));
+    hold
+}
+
+/// Reserves an amount on an account until the hold is captured, released or expires.
+///
+/// Only the account's owner or a teller may place a hold.
+#[ic_cdk::update]
+fn place_hold(account_id: u64, amount: Money, expiry: u64) -> Result<Hold, Error> {
+    // Validate the request before any state is touched
+    validation::validate_amount(amount)?;
+    let now = time();
+    if expiry <= now {
+        return Err(Error::InvalidHold {
+            msg: format!("Expiry {} is not in the future.", expiry),
+        });
+    }
+
+    let mut account = get_account_or_not_found(account_id)?;
+    let caller = caller();
+    if account.owner != caller && !has_role(&caller, Role::Teller) {
+        return Err(Error::Unauthorized {
+            msg: format!(
+                "Caller may not place a hold on the account with id={}.",
+                account_id
+            ),
+        });
+    }
+    lifecycle::ensure_can_send(&account)?;
+    if overdraft::spendable(&account)? < amount {
+        return Err(Error::InsufficientFunds {
+            msg: format!("Insufficient funds in account with id={}.", account_id),
+        });
+    }
+    account.held = account.held.checked_add(amount)?;
+
+    let id = HOLD_ID_COUNTER
+        .with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter.borrow_mut().set(current_value + 1)
+        })
+        .expect("Cannot increment hold ID counter");
+    let hold = Hold {
+        id,
+        account_id,
+        amount,
+        expires_at: expiry,
+        status: HoldStatus::Active,
+        placed_by: caller,
+        capture_transaction_id: None,
+        created_at: now,
+    };
+    do_insert_account(&account);
+    HOLDS.with(|h| h.borrow_mut().insert(id, hold.clone()));
+    ACCOUNT_HOLDS.with(|index| index.borrow_mut().insert((account_id, id), ()));
+    HOLD_EXPIRIES.with(|h| h.borrow_mut().insert((expiry, id), ()));
+    rearm();
+    Ok(hold)
+}
+
+/// Turns a hold into a transfer of `final_amount` to the receiver, lifting the rest of it.
+///
+/// The final amount may be lower than the amount held, but not higher. The transfer fee under
+/// the current schedule is charged on top of it, like on any other transfer.
+#[ic_cdk::update]
+fn capture_hold(hold_id: u64, final_amount: Money, receiver_id: u64) -> Result<Transaction, Error> {
+    let hold = get_active_hold(hold_id)?;
+    let caller = caller();
+    if hold.placed_by != caller && !has_role(&caller, Role::Teller) {
+        return Err(Error::Unauthorized {
+            msg: format!("Caller may not capture the hold with id={}.", hold_id),
+        });
+    }
+    validation::validate_amount(final_amount)?;
+    if final_amount > hold.amount {
+        return Err(Error::InvalidHold {
+            msg: format!(
+                "Cannot capture {} from hold with id={} of {}.",
+                final_amount, hold_id, hold.amount
+            ),
+        });
+    }
+    if receiver_id == hold.account_id {
+        return Err(Error::SelfTransfer {
+            msg: format!(
+                "Cannot capture hold with id={} into its own account.",
+                hold_id
+            ),
+        });
+    }
+
+    let quote = fees::quote(final_amount)?;
+
+    // The reservation is lifted as part of the transfer, so nothing is stored if it fails
+    let account = without_hold(get_account_or_not_found(hold.account_id)?, &hold)?;
+    let receiver = get_account_or_not_found(receiver_id)?;
+    let transaction = do_transfer(
+        account,
+        receiver,
+        Transaction {
+            amount: final_amount,
+            fee: (quote.fee > Money::ZERO).then_some(quote.fee),
+            reference: Some(format!("Hold id={}", hold_id)),
+            ..Default::default()
+        },
+    )?;
+    end_hold(hold, HoldStatus::Captured, Some(transaction.id));
+    rearm();
+    Ok(transaction)
+}
+
+/// Lifts a hold without moving any funds.
+///
+/// The principal that placed the hold, the account's owner or a teller may release it.
+#[ic_cdk::update]
+fn release_hold(hold_id: u64) -> Result<Hold, Error> {
+    let hold = get_active_hold(hold_id)?;
+    let account = get_account_or_not_found(hold.account_id)?;
+    let caller = caller();
+    if hold.placed_by != caller && account.owner != caller && !has_role(&caller, Role::Teller) {
+        return Err(Error::Unauthorized {
+            msg: format!("Caller may not release the hold with id={}.", hold_id),
+        });
+    }
+
+    let hold = end_hold(hold, HoldStatus::Released, None);
+    rearm();
+    Ok(hold)
+}
+
+/// Retrieves the holds placed on an account, both active and ended.
+#[ic_cdk::query]
+fn get_account_holds(account_id: u64) -> Result<Vec<Hold>, Error> {
+    let account = get_account_or_not_found(account_id)?;
+    ensure_can_view(&account)?;
+    Ok(holds_on(account_id))
+}
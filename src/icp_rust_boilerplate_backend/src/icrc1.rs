@@ -0,0 +1,369 @@
+use crate::currency::{self, BASE_CURRENCY};
+use crate::dedup::WindowError;
+use crate::money::Money;
+use crate::{
+    _get_account, dedup, do_create_account, do_transfer, ensure_not_anonymous, find_account_id,
+    ledger, lifecycle, validation, Account, Transaction,
+};
+use candid::{CandidType, Encode, Nat, Principal};
+use ic_cdk::api::caller;
+
+/// Represents a 32-byte ICRC-1 subaccount.
+pub type Subaccount = [u8; 32];
+
+/// The subaccount used when an ICRC-1 account does not specify one.
+pub const DEFAULT_SUBACCOUNT: Subaccount = [0; 32];
+
+/// Human-readable name of the token.
+pub const TOKEN_NAME: &str = "Financial Transaction System";
+
+/// Ticker symbol of the token.
+pub const TOKEN_SYMBOL: &str = "FTS";
+
+/// Maximum length of an ICRC-1 transfer memo in bytes.
+pub const MAX_MEMO_LEN: usize = 32;
+
+/// How long a transfer with `created_at_time` set stays valid, in nanoseconds.
+pub const TRANSACTION_WINDOW_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+/// How far a client clock may run ahead of the canister clock, in nanoseconds.
+pub const PERMITTED_DRIFT_NANOS: u64 = 60 * 1_000_000_000;
+
+/// Represents an ICRC-1 account: a principal and an optional subaccount.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct IcrcAccount {
+    pub owner: Principal,
+    pub subaccount: Option<Subaccount>,
+}
+
+impl IcrcAccount {
+    /// Returns the subaccount, substituting the default subaccount if none is set.
+    pub fn effective_subaccount(&self) -> Subaccount {
+        self.subaccount.unwrap_or(DEFAULT_SUBACCOUNT)
+    }
+}
+
+/// Represents a value in the ICRC-1 metadata map.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub enum MetadataValue {
+    Nat(Nat),
+    Int(candid::Int),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+/// Represents a standard supported by this canister.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct StandardRecord {
+    pub name: String,
+    pub url: String,
+}
+
+/// Represents the argument of `icrc1_transfer`.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct TransferArg {
+    pub from_subaccount: Option<Subaccount>,
+    pub to: IcrcAccount,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+/// Represents the errors defined by the ICRC-1 standard for `icrc1_transfer`.
+#[derive(candid::CandidType, Serialize, Deserialize, Debug)]
+pub enum TransferError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+/// Error code reported in `GenericError` for requests this ledger refuses.
+pub const GENERIC_ERROR_CODE: u64 = 1;
+
+/// Builds a generic ICRC-1 transfer error.
+pub fn generic_error(message: String) -> TransferError {
+    TransferError::GenericError {
+        error_code: Nat::from(GENERIC_ERROR_CODE),
+        message,
+    }
+}
+
+/// Encodes a number as a subaccount, big-endian in the last eight bytes.
+pub fn subaccount_from_u64(n: u64) -> Subaccount {
+    let mut subaccount = DEFAULT_SUBACCOUNT;
+    subaccount[24..].copy_from_slice(&n.to_be_bytes());
+    subaccount
+}
+
+/// Converts a non-negative amount to an ICRC-1 `Nat`; negative amounts are reported as zero.
+pub fn money_to_nat(amount: Money) -> Nat {
+    Nat::from(u64::try_from(amount.minor_units()).unwrap_or(0))
+}
+
+/// Converts an ICRC-1 `Nat` to an amount, or `None` if it does not fit.
+pub fn nat_to_money(amount: &Nat) -> Option<Money> {
+    match amount.0.to_u64_digits().as_slice() {
+        [] => Some(Money::ZERO),
+        [units] => i64::try_from(*units).ok().map(Money::from_minor_units),
+        _ => None,
+    }
+}
+
+/// Returns the account stored under an ICRC-1 address, if there is one.
+pub fn find_account(address: &IcrcAccount) -> Option<Account> {
+    find_account_id(&address.owner, &address.effective_subaccount())
+        .and_then(|id| _get_account(&id))
+}
+
+/// Rejects requests made by the anonymous principal, which may not move funds.
+pub fn ensure_caller_not_anonymous() -> Result<(), TransferError> {
+    ensure_not_anonymous(&caller()).map_err(|e| generic_error(format!("{:?}", e)))
+}
+
+/// Transfers funds to an ICRC-1 address, opening an empty account for it if there is none.
+///
+/// The account is only opened once the transfer is known to succeed, so a failed transfer
+/// never leaves an empty account behind. The caller is expected to have checked the sender's
+/// spendable balance first. Addresses owned by the anonymous principal or by the canister
+/// itself are rejected, as nobody could ever spend from them.
+pub fn transfer_to_address(
+    sender_account: Account,
+    to: &IcrcAccount,
+    details: Transaction,
+) -> Result<Transaction, TransferError> {
+    if to.owner == Principal::anonymous() || to.owner == ic_cdk::id() {
+        return Err(generic_error(format!(
+            "Cannot transfer to an account owned by {}.",
+            to.owner
+        )));
+    }
+    if let Some(receiver_account) = find_account(to) {
+        return do_transfer(sender_account, receiver_account, details)
+            .map_err(|e| generic_error(format!("{:?}", e)));
+    }
+
+    // A new account can always receive, so the sender is the only side left to check
+    lifecycle::ensure_can_send(&sender_account).map_err(|e| generic_error(format!("{:?}", e)))?;
+    let subaccount = to.effective_subaccount();
+    let receiver_account =
+        do_create_account(to.owner, Some(subaccount), to.owner.to_text(), Money::ZERO)
+            .map_err(|e| generic_error(format!("{:?}", e)))?;
+
+    // Every check has passed, so a failure here is a bug and traps to undo opening the account
+    Ok(do_transfer(sender_account, receiver_account, details)
+        .expect("Cannot transfer to a newly opened account"))
+}
+
+/// Returns the fee charged on ICRC-1 transfers.
+///
+/// The configurable fee schedule only applies to `transfer_funds`; ICRC transfers stay free.
+pub fn transfer_fee() -> Money {
+    Money::ZERO
+}
+
+// Map window errors onto their ICRC-1 counterparts
+impl From<WindowError> for TransferError {
+    fn from(error: WindowError) -> Self {
+        match error {
+            WindowError::TooOld { .. } => TransferError::TooOld,
+            WindowError::CreatedInFuture { ledger_time, .. } => {
+                TransferError::CreatedInFuture { ledger_time }
+            }
+        }
+    }
+}
+
+/// Checks the `created_at_time` of a request against the transaction window.
+pub fn check_created_at_time(created_at_time: Option<u64>) -> Result<(), TransferError> {
+    match created_at_time {
+        Some(created_at_time) => Ok(dedup::check_created_at_time(created_at_time)?),
+        None => Ok(()),
+    }
+}
+
+/// Identifies an ICRC request for deduplication by its method name and its full argument.
+pub fn request_bytes<A: CandidType>(method: &str, arg: &A) -> Vec<u8> {
+    let mut bytes = method.as_bytes().to_vec();
+    bytes.extend(Encode!(arg).expect("Cannot encode the request"));
+    bytes
+}
+
+/// Returns a `Duplicate` error if the caller already made an identical request.
+///
+/// As ICRC-1 requires, only requests that set `created_at_time` are deduplicated.
+pub fn check_duplicate(request: &[u8], created_at_time: Option<u64>) -> Result<(), TransferError> {
+    dedup::prune_expired();
+    match created_at_time.and_then(|time| dedup::find_duplicate(&caller(), request, time)) {
+        Some(transaction_id) => Err(TransferError::Duplicate {
+            duplicate_of: Nat::from(transaction_id),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Remembers the transaction recorded for a request so that retries are reported as duplicates.
+pub fn remember(request: &[u8], created_at_time: Option<u64>, transaction_id: u64) {
+    if let Some(created_at_time) = created_at_time {
+        dedup::remember(&caller(), request, created_at_time, transaction_id);
+    }
+}
+
+/// Checks the fee and memo of an ICRC request.
+pub fn check_fee_and_memo(fee: &Option<Nat>, memo: &Option<Vec<u8>>) -> Result<(), TransferError> {
+    let expected_fee = transfer_fee();
+    if let Some(fee) = fee {
+        if nat_to_money(fee) != Some(expected_fee) {
+            return Err(TransferError::BadFee {
+                expected_fee: money_to_nat(expected_fee),
+            });
+        }
+    }
+    if memo.as_ref().is_some_and(|memo| memo.len() > MAX_MEMO_LEN) {
+        return Err(generic_error(format!(
+            "Memo must not be longer than {} bytes.",
+            MAX_MEMO_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the name of the token.
+#[ic_cdk::query]
+fn icrc1_name() -> String {
+    TOKEN_NAME.to_string()
+}
+
+/// Returns the symbol of the token.
+#[ic_cdk::query]
+fn icrc1_symbol() -> String {
+    TOKEN_SYMBOL.to_string()
+}
+
+/// Returns the number of decimals the token uses.
+#[ic_cdk::query]
+fn icrc1_decimals() -> u8 {
+    Money::DECIMALS as u8
+}
+
+/// Returns the fee charged on each transfer.
+#[ic_cdk::query]
+fn icrc1_fee() -> Nat {
+    money_to_nat(transfer_fee())
+}
+
+/// Returns the token metadata.
+#[ic_cdk::query]
+fn icrc1_metadata() -> Vec<(String, MetadataValue)> {
+    vec![
+        (
+            "icrc1:name".to_string(),
+            MetadataValue::Text(TOKEN_NAME.to_string()),
+        ),
+        (
+            "icrc1:symbol".to_string(),
+            MetadataValue::Text(TOKEN_SYMBOL.to_string()),
+        ),
+        (
+            "icrc1:decimals".to_string(),
+            MetadataValue::Nat(Nat::from(Money::DECIMALS)),
+        ),
+        (
+            "icrc1:fee".to_string(),
+            MetadataValue::Nat(money_to_nat(transfer_fee())),
+        ),
+    ]
+}
+
+/// Returns the total amount of money held in customer accounts.
+#[ic_cdk::query]
+fn icrc1_total_supply() -> Nat {
+    money_to_nat(ledger::total_supply().unwrap_or(Money::ZERO))
+}
+
+/// Returns the minting account; money is minted through teller deposits, so there is none.
+#[ic_cdk::query]
+fn icrc1_minting_account() -> Option<IcrcAccount> {
+    None
+}
+
+/// Returns the balance of the specified ICRC-1 account.
+#[ic_cdk::query]
+fn icrc1_balance_of(account: IcrcAccount) -> Nat {
+    find_account_id(&account.owner, &account.effective_subaccount())
+        .and_then(|id| _get_account(&id))
+        .map_or(Nat::from(0u64), |account| money_to_nat(account.balance))
+}
+
+/// Returns the standards implemented by this canister.
+#[ic_cdk::query]
+fn icrc1_supported_standards() -> Vec<StandardRecord> {
+    vec![
+        StandardRecord {
+            name: "ICRC-1".to_string(),
+            url: "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-1".to_string(),
+        },
+        StandardRecord {
+            name: "ICRC-2".to_string(),
+            url: "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-2".to_string(),
+        },
+        StandardRecord {
+            name: "ICRC-3".to_string(),
+            url: "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-3".to_string(),
+        },
+    ]
+}
+
+/// Transfers funds from one of the caller's subaccounts to any ICRC-1 account.
+///
+/// Accounts that do not exist yet are opened on first receipt.
+#[ic_cdk::update]
+fn icrc1_transfer(arg: TransferArg) -> Result<Nat, TransferError> {
+    // Validate the request before any state is touched, rejecting retries of executed requests
+    ensure_caller_not_anonymous()?;
+    let request = request_bytes("icrc1_transfer", &arg);
+    check_created_at_time(arg.created_at_time)?;
+    check_duplicate(&request, arg.created_at_time)?;
+    check_fee_and_memo(&arg.fee, &arg.memo)?;
+    let amount = nat_to_money(&arg.amount)
+        .ok_or_else(|| generic_error("Amount is too large.".to_string()))?;
+    validation::validate_amount(amount).map_err(|e| generic_error(format!("{:?}", e)))?;
+
+    // Resolve the sender among the caller's own accounts
+    let from = IcrcAccount {
+        owner: caller(),
+        subaccount: arg.from_subaccount,
+    };
+    if from.owner == arg.to.owner && from.effective_subaccount() == arg.to.effective_subaccount() {
+        return Err(generic_error(
+            "Cannot transfer from an account to itself.".to_string(),
+        ));
+    }
+    let sender_account = find_account_id(&from.owner, &from.effective_subaccount())
+        .and_then(|id| _get_account(&id))
+        .ok_or(TransferError::InsufficientFunds {
+            balance: Nat::from(0u64),
+        })?;
+    let spendable = currency::spendable_in(&sender_account, BASE_CURRENCY)
+        .map_err(|e| generic_error(format!("{:?}", e)))?;
+    if spendable < amount {
+        return Err(TransferError::InsufficientFunds {
+            balance: money_to_nat(spendable),
+        });
+    }
+
+    // Move the funds, opening an account for the receiver if necessary
+    let details = Transaction {
+        amount,
+        memo: arg.memo,
+        ..Default::default()
+    };
+    let transaction = transfer_to_address(sender_account, &arg.to, details)?;
+    remember(&request, arg.created_at_time, transaction.id);
+    Ok(Nat::from(transaction.id))
+}
@@ -0,0 +1,369 @@
+use crate::currency::{self, BASE_CURRENCY};
+use crate::icrc1::{
+    check_created_at_time, check_duplicate, check_fee_and_memo, ensure_caller_not_anonymous,
+    generic_error, money_to_nat, nat_to_money, remember, request_bytes, transfer_to_address,
+    IcrcAccount, Subaccount, TransferError,
+};
+use crate::money::Money;
+use crate::roles::StorablePrincipal;
+use crate::{
+    _get_account, find_account_id, record_transaction, validation, Memory, Transaction,
+    TransactionKind, MEMORY_MANAGER,
+};
+use candid::{Decode, Encode, Nat};
+use ic_cdk::api::{caller, time};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+/// Represents the argument of `icrc2_approve`.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct ApproveArgs {
+    pub from_subaccount: Option<Subaccount>,
+    pub spender: IcrcAccount,
+    pub amount: Nat,
+    pub expected_allowance: Option<Nat>,
+    pub expires_at: Option<u64>,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+/// Represents the errors defined by the ICRC-2 standard for `icrc2_approve`.
+#[derive(candid::CandidType, Serialize, Deserialize, Debug)]
+pub enum ApproveError {
+    BadFee { expected_fee: Nat },
+    InsufficientFunds { balance: Nat },
+    AllowanceChanged { current_allowance: Nat },
+    Expired { ledger_time: u64 },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+/// Represents the argument of `icrc2_allowance`.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct AllowanceArgs {
+    pub account: IcrcAccount,
+    pub spender: IcrcAccount,
+}
+
+/// Represents an allowance as reported by `icrc2_allowance`.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct Allowance {
+    pub allowance: Nat,
+    pub expires_at: Option<u64>,
+}
+
+/// Represents the argument of `icrc2_transfer_from`.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct TransferFromArgs {
+    pub spender_subaccount: Option<Subaccount>,
+    pub from: IcrcAccount,
+    pub to: IcrcAccount,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<Vec<u8>>,
+    pub created_at_time: Option<u64>,
+}
+
+/// Represents the errors defined by the ICRC-2 standard for `icrc2_transfer_from`.
+#[derive(candid::CandidType, Serialize, Deserialize, Debug)]
+pub enum TransferFromError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+// Map the errors shared with ICRC-1 onto their ICRC-2 approve counterparts
+impl From<TransferError> for ApproveError {
+    fn from(error: TransferError) -> Self {
+        match error {
+            TransferError::BadFee { expected_fee } => ApproveError::BadFee { expected_fee },
+            TransferError::InsufficientFunds { balance } => {
+                ApproveError::InsufficientFunds { balance }
+            }
+            TransferError::TooOld => ApproveError::TooOld,
+            TransferError::CreatedInFuture { ledger_time } => {
+                ApproveError::CreatedInFuture { ledger_time }
+            }
+            TransferError::Duplicate { duplicate_of } => ApproveError::Duplicate { duplicate_of },
+            TransferError::TemporarilyUnavailable => ApproveError::TemporarilyUnavailable,
+            TransferError::BadBurn { .. } => ApproveError::GenericError {
+                error_code: Nat::from(crate::icrc1::GENERIC_ERROR_CODE),
+                message: "Approvals cannot burn funds.".to_string(),
+            },
+            TransferError::GenericError {
+                error_code,
+                message,
+            } => ApproveError::GenericError {
+                error_code,
+                message,
+            },
+        }
+    }
+}
+
+// Map the errors shared with ICRC-1 onto their ICRC-2 transfer_from counterparts
+impl From<TransferError> for TransferFromError {
+    fn from(error: TransferError) -> Self {
+        match error {
+            TransferError::BadFee { expected_fee } => TransferFromError::BadFee { expected_fee },
+            TransferError::BadBurn { min_burn_amount } => {
+                TransferFromError::BadBurn { min_burn_amount }
+            }
+            TransferError::InsufficientFunds { balance } => {
+                TransferFromError::InsufficientFunds { balance }
+            }
+            TransferError::TooOld => TransferFromError::TooOld,
+            TransferError::CreatedInFuture { ledger_time } => {
+                TransferFromError::CreatedInFuture { ledger_time }
+            }
+            TransferError::Duplicate { duplicate_of } => {
+                TransferFromError::Duplicate { duplicate_of }
+            }
+            TransferError::TemporarilyUnavailable => TransferFromError::TemporarilyUnavailable,
+            TransferError::GenericError {
+                error_code,
+                message,
+            } => TransferFromError::GenericError {
+                error_code,
+                message,
+            },
+        }
+    }
+}
+
+/// Represents an allowance as persisted in stable memory.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Default)]
+struct StoredAllowance {
+    amount: Money,
+    expires_at: Option<u64>,
+}
+
+impl StoredAllowance {
+    // An expired allowance behaves exactly like no allowance at all
+    fn is_active(&self, now: u64) -> bool {
+        !matches!(self.expires_at, Some(expires_at) if expires_at <= now)
+    }
+}
+
+impl Storable for StoredAllowance {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for StoredAllowance {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Represents the key of an allowance: the approving account ID and the spender's address.
+type AllowanceKey = (u64, (StorablePrincipal, Subaccount));
+
+// Thread-local storage for ICRC-2 allowances
+thread_local! {
+    static ALLOWANCES: RefCell<StableBTreeMap<AllowanceKey, StoredAllowance, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9)))
+    ));
+}
+
+// Helper function to build the allowance key for an account and a spender
+fn allowance_key(account_id: u64, spender: &IcrcAccount) -> AllowanceKey {
+    (
+        account_id,
+        (
+            StorablePrincipal(spender.owner),
+            spender.effective_subaccount(),
+        ),
+    )
+}
+
+// Helper function to read the allowance that is currently in effect, if any
+fn active_allowance(key: &AllowanceKey, now: u64) -> Option<StoredAllowance> {
+    ALLOWANCES
+        .with(|allowances| allowances.borrow().get(key))
+        .filter(|allowance| allowance.is_active(now))
+}
+
+/// Allows a spender to transfer up to the given amount from one of the caller's subaccounts.
+#[ic_cdk::update]
+fn icrc2_approve(args: ApproveArgs) -> Result<Nat, ApproveError> {
+    // Validate the request before any state is touched, rejecting retries of executed requests
+    ensure_caller_not_anonymous()?;
+    let now = time();
+    let request = request_bytes("icrc2_approve", &args);
+    check_created_at_time(args.created_at_time)?;
+    check_duplicate(&request, args.created_at_time)?;
+    check_fee_and_memo(&args.fee, &args.memo)?;
+    if args.expires_at.is_some_and(|expires_at| expires_at <= now) {
+        return Err(ApproveError::Expired { ledger_time: now });
+    }
+    let amount = nat_to_money(&args.amount)
+        .ok_or_else(|| generic_error("Amount is too large.".to_string()))?;
+
+    // Resolve the approving account among the caller's own accounts
+    let from = IcrcAccount {
+        owner: caller(),
+        subaccount: args.from_subaccount,
+    };
+    if from.owner == args.spender.owner
+        && from.effective_subaccount() == args.spender.effective_subaccount()
+    {
+        return Err(generic_error("Cannot approve an allowance to oneself.".to_string()).into());
+    }
+    let account = find_account_id(&from.owner, &from.effective_subaccount())
+        .and_then(|id| _get_account(&id))
+        .ok_or(ApproveError::InsufficientFunds {
+            balance: Nat::from(0u64),
+        })?;
+
+    // Compare against the allowance the client expects to replace
+    let key = allowance_key(account.id, &args.spender);
+    let current = active_allowance(&key, now).map_or(Money::ZERO, |allowance| allowance.amount);
+    if let Some(expected_allowance) = &args.expected_allowance {
+        if nat_to_money(expected_allowance) != Some(current) {
+            return Err(ApproveError::AllowanceChanged {
+                current_allowance: money_to_nat(current),
+            });
+        }
+    }
+
+    // Record the approval, then store the new allowance
+    let transaction = record_transaction(Transaction {
+        kind: TransactionKind::Approval,
+        sender_id: Some(account.id),
+        amount,
+        memo: args.memo,
+        spender: Some(args.spender),
+        expires_at: args.expires_at,
+        ..Default::default()
+    })
+    .map_err(|e| generic_error(format!("{:?}", e)))?;
+
+    // An allowance set to zero is dropped entirely
+    ALLOWANCES.with(|allowances| {
+        let mut allowances = allowances.borrow_mut();
+        if amount == Money::ZERO {
+            allowances.remove(&key);
+        } else {
+            allowances.insert(
+                key,
+                StoredAllowance {
+                    amount,
+                    expires_at: args.expires_at,
+                },
+            );
+        }
+    });
+
+    remember(&request, args.created_at_time, transaction.id);
+    Ok(Nat::from(transaction.id))
+}
+
+/// Returns the allowance a spender currently has on an account.
+#[ic_cdk::query]
+fn icrc2_allowance(args: AllowanceArgs) -> Allowance {
+    find_account_id(&args.account.owner, &args.account.effective_subaccount())
+        .and_then(|id| active_allowance(&allowance_key(id, &args.spender), time()))
+        .map_or(
+            Allowance {
+                allowance: Nat::from(0u64),
+                expires_at: None,
+            },
+            |allowance| Allowance {
+                allowance: money_to_nat(allowance.amount),
+                expires_at: allowance.expires_at,
+            },
+        )
+}
+
+/// Transfers funds from an account that has approved the caller as a spender.
+#[ic_cdk::update]
+fn icrc2_transfer_from(args: TransferFromArgs) -> Result<Nat, TransferFromError> {
+    // Validate the request before any state is touched, rejecting retries of executed requests
+    ensure_caller_not_anonymous()?;
+    let now = time();
+    let request = request_bytes("icrc2_transfer_from", &args);
+    check_created_at_time(args.created_at_time)?;
+    check_duplicate(&request, args.created_at_time)?;
+    check_fee_and_memo(&args.fee, &args.memo)?;
+    let amount = nat_to_money(&args.amount)
+        .ok_or_else(|| generic_error("Amount is too large.".to_string()))?;
+    validation::validate_amount(amount).map_err(|e| generic_error(format!("{:?}", e)))?;
+    if args.from.owner == args.to.owner
+        && args.from.effective_subaccount() == args.to.effective_subaccount()
+    {
+        return Err(generic_error("Cannot transfer from an account to itself.".to_string()).into());
+    }
+
+    // Resolve the source account and check the caller's allowance on it
+    let spender = IcrcAccount {
+        owner: caller(),
+        subaccount: args.spender_subaccount,
+    };
+    let sender_account = find_account_id(&args.from.owner, &args.from.effective_subaccount())
+        .and_then(|id| _get_account(&id))
+        .ok_or(TransferFromError::InsufficientFunds {
+            balance: Nat::from(0u64),
+        })?;
+    let key = allowance_key(sender_account.id, &spender);
+    let allowance = active_allowance(&key, now).unwrap_or_default();
+    if allowance.amount < amount {
+        return Err(TransferFromError::InsufficientAllowance {
+            allowance: money_to_nat(allowance.amount),
+        });
+    }
+    let spendable = currency::spendable_in(&sender_account, BASE_CURRENCY)
+        .map_err(|e| generic_error(format!("{:?}", e)))?;
+    if spendable < amount {
+        return Err(TransferFromError::InsufficientFunds {
+            balance: money_to_nat(spendable),
+        });
+    }
+
+    let remaining = allowance
+        .amount
+        .checked_sub(amount)
+        .map_err(|e| generic_error(format!("{:?}", e)))?;
+
+    // Move the funds to the receiver, then consume the used part of the allowance
+    let details = Transaction {
+        amount,
+        memo: args.memo,
+        spender: Some(spender),
+        ..Default::default()
+    };
+    let transaction = transfer_to_address(sender_account, &args.to, details)?;
+    ALLOWANCES.with(|allowances| {
+        let mut allowances = allowances.borrow_mut();
+        if remaining == Money::ZERO {
+            allowances.remove(&key);
+        } else {
+            allowances.insert(
+                key,
+                StoredAllowance {
+                    amount: remaining,
+                    expires_at: allowance.expires_at,
+                },
+            );
+        }
+    });
+
+    remember(&request, args.created_at_time, transaction.id);
+    Ok(Nat::from(transaction.id))
+}
@@ -0,0 +1,577 @@
+use crate::certification;
+use crate::icrc1::{money_to_nat, IcrcAccount, DEFAULT_SUBACCOUNT};
+use crate::{
+    _get_transaction, transaction_count, transactions_in, Error, Memory, Transaction,
+    TransactionKind, MEMORY_MANAGER, TRANSACTIONS,
+};
+use candid::{Decode, Encode, Int, Nat, Principal};
+use ic_cdk::api::data_certificate;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{Cell, Storable};
+use sha2::{Digest, Sha256};
+use std::{borrow::Cow, cell::RefCell};
+
+/// Represents a SHA-256 hash.
+pub type Hash = [u8; 32];
+
+/// Maximum number of blocks returned by a single `icrc3_get_blocks` call.
+pub const MAX_BLOCKS_PER_REQUEST: u64 = 100;
+
+/// Represents a generic ICRC-3 value, used to encode blocks.
+#[derive(candid::CandidType, Deserialize, Clone)]
+pub enum Value {
+    Blob(Vec<u8>),
+    Text(String),
+    Nat(Nat),
+    Int(Int),
+    Array(Vec<Value>),
+    Map(Vec<(String, Value)>),
+}
+
+/// Represents a range of blocks requested from `icrc3_get_blocks`.
+#[derive(candid::CandidType, Deserialize, Clone)]
+pub struct GetBlocksArgs {
+    pub start: Nat,
+    pub length: Nat,
+}
+
+/// Represents a block together with its index in the log.
+#[derive(candid::CandidType, Deserialize, Clone)]
+pub struct BlockWithId {
+    pub id: Nat,
+    pub block: Value,
+}
+
+candid::define_function!(pub GetBlocksCallback : (Vec<GetBlocksArgs>) -> (GetBlocksResult) query);
+
+/// Represents blocks that were moved to an archive canister.
+#[derive(candid::CandidType, Deserialize, Clone)]
+pub struct ArchivedBlocks {
+    pub args: Vec<GetBlocksArgs>,
+    pub callback: GetBlocksCallback,
+}
+
+/// Represents the result of `icrc3_get_blocks`.
+#[derive(candid::CandidType, Deserialize, Clone)]
+pub struct GetBlocksResult {
+    pub log_length: Nat,
+    pub blocks: Vec<BlockWithId>,
+    pub archived_blocks: Vec<ArchivedBlocks>,
+}
+
+/// Represents the certificate of the last block together with its witness.
+#[derive(candid::CandidType, Deserialize, Clone)]
+pub struct DataCertificate {
+    pub certificate: Vec<u8>,
+    pub hash_tree: Vec<u8>,
+}
+
+/// Represents the argument of `icrc3_get_archives`.
+#[derive(candid::CandidType, Deserialize, Clone)]
+pub struct GetArchivesArgs {
+    pub from: Option<Principal>,
+}
+
+/// Represents an archive canister holding part of the block log.
+#[derive(candid::CandidType, Deserialize, Clone)]
+pub struct ArchiveInfo {
+    pub canister_id: Principal,
+    pub start: Nat,
+    pub end: Nat,
+}
+
+/// Represents a block type this canister produces.
+#[derive(candid::CandidType, Deserialize, Clone)]
+pub struct SupportedBlockType {
+    pub block_type: String,
+    pub url: String,
+}
+
+// Represents the index and hash of the last block appended to the log
+//
+// The hash is kept apart from the block itself, so that the next block is chained to it even
+// if the stored block later turns out to be corrupt.
+#[derive(candid::CandidType, Deserialize, Clone, Default)]
+struct StoredTip(Option<(u64, Hash)>);
+
+impl Storable for StoredTip {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+// Thread-local storage for the tip of the log
+thread_local! {
+    static TIP: RefCell<Cell<StoredTip, Memory>> = RefCell::new(
+        Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(29))), StoredTip::default())
+            .expect("Cannot create the tip cell")
+    );
+}
+
+// Helper function to compute the SHA-256 digest of some bytes
+fn sha256(bytes: &[u8]) -> Hash {
+    Sha256::digest(bytes).into()
+}
+
+/// Computes the representation-independent hash of a value, as defined by ICRC-3.
+pub fn hash_value(value: &Value) -> Hash {
+    match value {
+        Value::Blob(bytes) => sha256(bytes),
+        Value::Text(text) => sha256(text.as_bytes()),
+        Value::Nat(n) => {
+            let mut buf = Vec::new();
+            n.encode(&mut buf).expect("Cannot LEB128-encode a Nat");
+            sha256(&buf)
+        }
+        Value::Int(i) => {
+            let mut buf = Vec::new();
+            i.encode(&mut buf).expect("Cannot SLEB128-encode an Int");
+            sha256(&buf)
+        }
+        Value::Array(values) => {
+            let mut hasher = Sha256::new();
+            for value in values {
+                hasher.update(hash_value(value));
+            }
+            hasher.finalize().into()
+        }
+        Value::Map(entries) => {
+            let mut pairs: Vec<Vec<u8>> = entries
+                .iter()
+                .map(|(key, value)| {
+                    let mut pair = sha256(key.as_bytes()).to_vec();
+                    pair.extend_from_slice(&hash_value(value));
+                    pair
+                })
+                .collect();
+            pairs.sort();
+            let mut hasher = Sha256::new();
+            for pair in pairs {
+                hasher.update(pair);
+            }
+            hasher.finalize().into()
+        }
+    }
+}
+
+// Helper function to encode an ICRC-1 account as a block value
+fn account_value(account: &IcrcAccount) -> Value {
+    let mut parts = vec![Value::Blob(account.owner.as_slice().to_vec())];
+    if account.effective_subaccount() != DEFAULT_SUBACCOUNT {
+        parts.push(Value::Blob(account.effective_subaccount().to_vec()));
+    }
+    Value::Array(parts)
+}
+
+/// Block types of transfers, deposits and withdrawals that involve a currency other than the
+/// base currency, which the ICRC-3 block types cannot express.
+///
+/// They carry the same fields as `1xfer`, `1mint` and `1burn`, with `amt` in minor units of
+/// `tx.currency`, the base currency if absent, and `tx.converted_currency` and
+/// `tx.converted_amt` for conversions.
+pub const FOREIGN_BLOCK_TYPES: [&str; 3] = ["fts_fxfer", "fts_fmint", "fts_fburn"];
+
+/// Encodes a transaction as an ICRC-3 block.
+///
+/// Only fields that are set are included, so that fields added to `Transaction` later
+/// do not change the hash of blocks recorded before they existed.
+pub fn transaction_to_block(transaction: &Transaction) -> Value {
+    let foreign = transaction.currency.is_some() || transaction.conversion.is_some();
+    let btype = match transaction.kind {
+        TransactionKind::Transfer if foreign => FOREIGN_BLOCK_TYPES[0],
+        TransactionKind::Deposit if foreign => FOREIGN_BLOCK_TYPES[1],
+        TransactionKind::Withdrawal if foreign => FOREIGN_BLOCK_TYPES[2],
+        TransactionKind::Transfer if transaction.spender.is_some() => "2xfer",
+        TransactionKind::Transfer => "1xfer",
+        TransactionKind::Deposit => "1mint",
+        TransactionKind::Withdrawal => "1burn",
+        TransactionKind::Approval => "2approve",
+        // Escrowed funds are out of circulation until the escrow is settled
+        TransactionKind::EscrowHold => "1burn",
+        TransactionKind::EscrowRelease | TransactionKind::EscrowRefund => "1mint",
+    };
+
+    let mut tx = vec![(
+        "amt".to_string(),
+        Value::Nat(money_to_nat(transaction.amount)),
+    )];
+    if let Some(from) = &transaction.from {
+        tx.push(("from".to_string(), account_value(from)));
+    }
+    if let Some(to) = &transaction.to {
+        tx.push(("to".to_string(), account_value(to)));
+    }
+    if let Some(spender) = &transaction.spender {
+        tx.push(("spender".to_string(), account_value(spender)));
+    }
+    if let Some(memo) = &transaction.memo {
+        tx.push(("memo".to_string(), Value::Blob(memo.clone())));
+    }
+    if let Some(expires_at) = transaction.expires_at {
+        tx.push(("expires_at".to_string(), Value::Nat(Nat::from(expires_at))));
+    }
+    if let Some(reference) = &transaction.reference {
+        tx.push(("reference".to_string(), Value::Text(reference.clone())));
+    }
+    if let Some(reverses) = transaction.reverses {
+        tx.push(("reverses".to_string(), Value::Nat(Nat::from(reverses))));
+    }
+    if let Some(batch_id) = transaction.batch_id {
+        tx.push(("batch_id".to_string(), Value::Nat(Nat::from(batch_id))));
+    }
+    if let Some(currency) = &transaction.currency {
+        tx.push(("currency".to_string(), Value::Text(currency.clone())));
+    }
+    if let Some(conversion) = &transaction.conversion {
+        tx.push((
+            "converted_currency".to_string(),
+            Value::Text(conversion.currency.clone()),
+        ));
+        tx.push((
+            "converted_amt".to_string(),
+            Value::Nat(money_to_nat(conversion.amount)),
+        ));
+    }
+
+    let mut block = vec![
+        ("btype".to_string(), Value::Text(btype.to_string())),
+        (
+            "ts".to_string(),
+            Value::Nat(Nat::from(transaction.timestamp)),
+        ),
+        ("tx".to_string(), Value::Map(tx)),
+    ];
+    if let Some(fee) = transaction.fee {
+        block.push(("fee".to_string(), Value::Nat(money_to_nat(fee))));
+    }
+    if let Some(phash) = transaction.phash {
+        block.push(("phash".to_string(), Value::Blob(phash.to_vec())));
+    }
+    Value::Map(block)
+}
+
+/// Returns the index and hash of the last block in the log, if any.
+pub fn tip() -> Option<(u64, Hash)> {
+    TIP.with(|t| t.borrow().get().0)
+}
+
+/// Records the block just appended to the log as its new tip.
+pub fn set_tip(id: u64, hash: Hash) {
+    TIP.with(|t| t.borrow_mut().set(StoredTip(Some((id, hash)))))
+        .expect("Cannot store the tip of the log");
+}
+
+/// Returns the hash the next block is chained to, or none if the log is empty.
+///
+/// The log cannot be extended while the hash of its last block is unknown, which only happens
+/// if that block was already unreadable when the tip was first stored; it has to be repaired
+/// first.
+pub fn chain_hash() -> Result<Option<Hash>, Error> {
+    let last_id = match TRANSACTIONS.with(|s| s.borrow().last_key_value()) {
+        Some((id, _)) => id,
+        None => return Ok(None),
+    };
+    match tip() {
+        Some((id, hash)) if id == last_id => Ok(Some(hash)),
+        _ => Err(Error::TipUnavailable {
+            msg: format!(
+                "The hash of block id={} is unknown; it must be repaired before the log can grow.",
+                last_id
+            ),
+        }),
+    }
+}
+
+/// Stores the tip of the log from its last block, unless it is already stored.
+///
+/// Logs written before the tip was stored have it computed once from their last block; if that
+/// block is unreadable, the tip stays unknown until the block is repaired.
+pub fn store_tip_of_log() {
+    if tip().is_some() {
+        return;
+    }
+    let last = TRANSACTIONS
+        .with(|s| s.borrow().last_key_value())
+        .and_then(|(id, _)| _get_transaction(&id));
+    if let Some(transaction) = last {
+        set_tip(
+            transaction.id,
+            hash_value(&transaction_to_block(&transaction)),
+        );
+    }
+}
+
+/// Returns the blocks in the requested ranges.
+#[ic_cdk::query]
+fn icrc3_get_blocks(args: Vec<GetBlocksArgs>) -> GetBlocksResult {
+    let log_length = transaction_count();
+    let mut blocks = Vec::new();
+    for arg in args {
+        let start = u64::try_from(arg.start.0).unwrap_or(u64::MAX);
+        let length = u64::try_from(arg.length.0).unwrap_or(u64::MAX);
+        let budget = MAX_BLOCKS_PER_REQUEST.saturating_sub(blocks.len() as u64);
+        let end = start.saturating_add(length.min(budget)).min(log_length);
+        if start >= end {
+            continue;
+        }
+        for transaction in transactions_in(start..end) {
+            blocks.push(BlockWithId {
+                id: Nat::from(transaction.id),
+                block: transaction_to_block(&transaction),
+            });
+        }
+    }
+    GetBlocksResult {
+        log_length: Nat::from(log_length),
+        blocks,
+        archived_blocks: Vec::new(),
+    }
+}
+
+/// Returns the certificate for the tip of the log, with the witness needed to verify it.
+#[ic_cdk::query]
+fn icrc3_get_tip_certificate() -> Option<DataCertificate> {
+    let certificate = data_certificate()?;
+    let witness = certification::tip_witness()?;
+    Some(DataCertificate {
+        certificate,
+        hash_tree: certification::encode_witness(&witness),
+    })
+}
+
+/// Returns the archive canisters; all blocks are kept in this canister.
+#[ic_cdk::query]
+fn icrc3_get_archives(_args: GetArchivesArgs) -> Vec<ArchiveInfo> {
+    Vec::new()
+}
+
+/// Returns the block types this canister produces.
+#[ic_cdk::query]
+fn icrc3_supported_block_types() -> Vec<SupportedBlockType> {
+    let url = "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-3";
+    ["1xfer", "1mint", "1burn", "2xfer", "2approve"]
+        .iter()
+        .chain(FOREIGN_BLOCK_TYPES.iter())
+        .map(|block_type| SupportedBlockType {
+            block_type: block_type.to_string(),
+            url: url.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::Conversion;
+    use crate::money::Money;
+
+    fn hex(hash: Hash) -> String {
+        hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn entry(key: &str, value: Value) -> (String, Value) {
+        (key.to_string(), value)
+    }
+
+    fn btype(transaction: Transaction) -> String {
+        match transaction_to_block(&transaction) {
+            Value::Map(entries) => match &entries[0] {
+                (key, Value::Text(btype)) if key == "btype" => btype.clone(),
+                _ => panic!("The block does not start with its type"),
+            },
+            _ => panic!("The block is not a map"),
+        }
+    }
+
+    // Expected hashes are the examples given in the ICRC-3 standard
+    #[test]
+    fn hashes_nat_as_leb128() {
+        assert_eq!(
+            hex(hash_value(&Value::Nat(Nat::from(42u64)))),
+            "684888c0ebb17f374298b65ee2807526c066094c701bcc7ebbe1c1095f494fc1"
+        );
+    }
+
+    #[test]
+    fn hashes_int_as_sleb128() {
+        assert_eq!(
+            hex(hash_value(&Value::Int(Int::from(-42)))),
+            "de5a6f78116eca62d7fc5ce159d23ae6b889b365a1739ad2cf36f925a140d0cc"
+        );
+    }
+
+    #[test]
+    fn hashes_text_as_utf8() {
+        assert_eq!(
+            hex(hash_value(&Value::Text("Hello, World!".to_string()))),
+            "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f"
+        );
+    }
+
+    #[test]
+    fn hashes_blob_as_its_bytes() {
+        assert_eq!(
+            hex(hash_value(&Value::Blob(vec![1, 2, 3, 4]))),
+            "9f64a747e1b97f131fabb6b447296c9b6f0201e79fb3c5356e6c77e89b6a806a"
+        );
+    }
+
+    #[test]
+    fn hashes_array_from_its_element_hashes() {
+        let array = Value::Array(vec![
+            Value::Nat(Nat::from(3u64)),
+            Value::Text("foo".to_string()),
+            Value::Blob(vec![5, 6]),
+        ]);
+        assert_eq!(
+            hex(hash_value(&array)),
+            "514a04011caa503990d446b7dec5d79e19c221ae607fb08b2848c67734d468d6"
+        );
+    }
+
+    #[test]
+    fn hashes_map_independently_of_entry_order() {
+        let from = vec![
+            0x00, 0xab, 0xcd, 0xef, 0x00, 0x12, 0x34, 0x00, 0x56, 0x78, 0x9a, 0x00, 0xbc, 0xde,
+            0xf0, 0x00, 0x01, 0x23, 0x45, 0x67, 0x89, 0x00, 0xab, 0xcd, 0xef, 0x01,
+        ];
+        let mut to = from.clone();
+        to[2] = 0x0d;
+        let mut entries = vec![
+            entry("from", Value::Blob(from)),
+            entry("to", Value::Blob(to)),
+            entry("amount", Value::Nat(Nat::from(42u64))),
+            entry("created_at", Value::Nat(Nat::from(1_699_218_263u64))),
+            entry("memo", Value::Nat(Nat::from(0u64))),
+        ];
+        let expected = "c56ece650e1de4269c5bdeff7875949e3e2033f85b2d193c2ff4f7f78bdcfc75";
+        assert_eq!(hex(hash_value(&Value::Map(entries.clone()))), expected);
+        entries.reverse();
+        assert_eq!(hex(hash_value(&Value::Map(entries))), expected);
+    }
+
+    #[test]
+    fn encodes_a_transfer_with_only_the_fields_that_are_set() {
+        let owner = Principal::from_slice(&[7; 29]);
+        let subaccount = [1; 32];
+        let transaction = Transaction {
+            id: 5,
+            amount: Money::from_minor_units(250),
+            fee: Some(Money::from_minor_units(3)),
+            timestamp: 1_000,
+            from: Some(IcrcAccount {
+                owner,
+                subaccount: None,
+            }),
+            to: Some(IcrcAccount {
+                owner,
+                subaccount: Some(subaccount),
+            }),
+            memo: Some(vec![9]),
+            phash: Some([2; 32]),
+            ..Default::default()
+        };
+        let expected = Value::Map(vec![
+            entry("btype", Value::Text("1xfer".to_string())),
+            entry("ts", Value::Nat(Nat::from(1_000u64))),
+            entry(
+                "tx",
+                Value::Map(vec![
+                    entry("amt", Value::Nat(Nat::from(250u64))),
+                    entry("from", Value::Array(vec![Value::Blob(vec![7; 29])])),
+                    entry(
+                        "to",
+                        Value::Array(vec![Value::Blob(vec![7; 29]), Value::Blob(vec![1; 32])]),
+                    ),
+                    entry("memo", Value::Blob(vec![9])),
+                ]),
+            ),
+            entry("fee", Value::Nat(Nat::from(3u64))),
+            entry("phash", Value::Blob(vec![2; 32])),
+        ]);
+        assert_eq!(
+            hash_value(&transaction_to_block(&transaction)),
+            hash_value(&expected)
+        );
+    }
+
+    #[test]
+    fn encodes_block_types_by_transaction_kind() {
+        let spender = IcrcAccount {
+            owner: Principal::anonymous(),
+            subaccount: None,
+        };
+        assert_eq!(btype(Transaction::default()), "1xfer");
+        assert_eq!(
+            btype(Transaction {
+                spender: Some(spender),
+                ..Default::default()
+            }),
+            "2xfer"
+        );
+        assert_eq!(
+            btype(Transaction {
+                kind: TransactionKind::Deposit,
+                ..Default::default()
+            }),
+            "1mint"
+        );
+        assert_eq!(
+            btype(Transaction {
+                kind: TransactionKind::Withdrawal,
+                ..Default::default()
+            }),
+            "1burn"
+        );
+        assert_eq!(
+            btype(Transaction {
+                kind: TransactionKind::Approval,
+                spender: Some(spender),
+                ..Default::default()
+            }),
+            "2approve"
+        );
+    }
+
+    #[test]
+    fn encodes_other_currencies_with_their_own_block_types() {
+        let eur = Some("EUR".to_string());
+        assert_eq!(
+            btype(Transaction {
+                currency: eur.clone(),
+                ..Default::default()
+            }),
+            "fts_fxfer"
+        );
+        assert_eq!(
+            btype(Transaction {
+                conversion: Some(Conversion {
+                    currency: "EUR".to_string(),
+                    amount: Money::from_minor_units(90),
+                }),
+                ..Default::default()
+            }),
+            "fts_fxfer"
+        );
+        assert_eq!(
+            btype(Transaction {
+                kind: TransactionKind::Deposit,
+                currency: eur.clone(),
+                ..Default::default()
+            }),
+            "fts_fmint"
+        );
+        assert_eq!(
+            btype(Transaction {
+                kind: TransactionKind::Withdrawal,
+                currency: eur,
+                ..Default::default()
+            }),
+            "fts_fburn"
+        );
+    }
+}
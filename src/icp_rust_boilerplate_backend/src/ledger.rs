@@ -0,0 +1,373 @@
+use crate::currency::{self, BASE_CURRENCY};
+use crate::money::Money;
+use crate::roles::caller_is_auditor;
+use crate::{Error, Memory, MEMORY_MANAGER};
+use candid::{Decode, Encode};
+use ic_cdk::api::time;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+/// Represents an internal account of the canister's own books.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SystemAccount {
+    /// Receives the debit side of every deposit.
+    CashIn,
+    /// Receives the credit side of every withdrawal.
+    CashOut,
+    /// Collects fees charged on transactions.
+    FeeIncome,
+    /// Holds amounts that cannot yet be attributed to a customer.
+    Suspense,
+    /// Holds funds placed in escrow until they are released or refunded.
+    Escrow,
+    /// Takes in the amount sent and pays out the amount received when a transfer is converted
+    /// between currencies.
+    Exchange,
+}
+
+/// Represents an account in the general ledger, either a customer account or a system account.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerAccount {
+    Customer(u64),
+    System(SystemAccount),
+}
+
+impl LedgerAccount {
+    // Encode the ledger account as a fixed-size stable map key
+    fn key(&self) -> (u8, u64) {
+        match self {
+            LedgerAccount::Customer(id) => (0, *id),
+            LedgerAccount::System(SystemAccount::CashIn) => (1, 0),
+            LedgerAccount::System(SystemAccount::CashOut) => (1, 1),
+            LedgerAccount::System(SystemAccount::FeeIncome) => (1, 2),
+            LedgerAccount::System(SystemAccount::Suspense) => (1, 3),
+            LedgerAccount::System(SystemAccount::Escrow) => (1, 4),
+            LedgerAccount::System(SystemAccount::Exchange) => (1, 5),
+        }
+    }
+
+    // Decode a ledger account from its stable map key
+    fn from_key(key: (u8, u64)) -> Self {
+        match key {
+            (0, id) => LedgerAccount::Customer(id),
+            (_, 0) => LedgerAccount::System(SystemAccount::CashIn),
+            (_, 1) => LedgerAccount::System(SystemAccount::CashOut),
+            (_, 2) => LedgerAccount::System(SystemAccount::FeeIncome),
+            (_, 4) => LedgerAccount::System(SystemAccount::Escrow),
+            (_, 5) => LedgerAccount::System(SystemAccount::Exchange),
+            _ => LedgerAccount::System(SystemAccount::Suspense),
+        }
+    }
+}
+
+/// Represents the side of a journal line.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum EntrySide {
+    Debit,
+    Credit,
+}
+
+/// Represents a single debit or credit leg of a journal entry.
+///
+/// The amount is in `currency`, which is none for the base currency.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, PartialEq)]
+pub struct JournalLine {
+    pub account: LedgerAccount,
+    pub side: EntrySide,
+    pub amount: Money,
+    pub currency: Option<String>,
+}
+
+impl JournalLine {
+    /// Creates a debit leg against the given ledger account.
+    pub fn debit(account: LedgerAccount, amount: Money) -> Self {
+        Self {
+            account,
+            side: EntrySide::Debit,
+            amount,
+            currency: None,
+        }
+    }
+
+    /// Creates a credit leg against the given ledger account.
+    pub fn credit(account: LedgerAccount, amount: Money) -> Self {
+        Self {
+            account,
+            side: EntrySide::Credit,
+            amount,
+            currency: None,
+        }
+    }
+
+    /// Moves the leg into the given currency; legs are in the base currency by default.
+    pub fn in_currency(self, currency: Option<String>) -> Self {
+        Self { currency, ..self }
+    }
+}
+
+/// Represents a balanced journal entry recorded for a transaction.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct JournalEntry {
+    pub transaction_id: u64,
+    pub lines: Vec<JournalLine>,
+    pub timestamp: u64,
+}
+
+impl Storable for JournalEntry {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for JournalEntry {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Represents the running debit and credit totals of a ledger account.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Default)]
+struct LedgerTotals {
+    debits: Money,
+    credits: Money,
+}
+
+impl Storable for LedgerTotals {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for LedgerTotals {
+    const MAX_SIZE: u32 = 64;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Represents one ledger account's line in the trial balance.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct TrialBalanceLine {
+    pub account: LedgerAccount,
+    pub debits: Money,
+    pub credits: Money,
+}
+
+/// Represents the trial balance of the general ledger in one currency; total debits always
+/// equal total credits.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct TrialBalance {
+    pub currency: String,
+    pub lines: Vec<TrialBalanceLine>,
+    pub total_debits: Money,
+    pub total_credits: Money,
+}
+
+// Key of a ledger account's totals in a currency other than the base currency
+type ForeignTotalsKey = ([u8; 3], (u8, u64));
+
+// Thread-local storage for the general ledger
+//
+// Totals in the base currency are keyed by ledger account; totals in any other currency are
+// keyed by the currency code and the ledger account.
+thread_local! {
+    static JOURNAL: RefCell<StableBTreeMap<u64, JournalEntry, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6)))
+    ));
+
+    static LEDGER_TOTALS: RefCell<StableBTreeMap<(u8, u64), LedgerTotals, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7)))
+    ));
+
+    static FOREIGN_LEDGER_TOTALS: RefCell<StableBTreeMap<ForeignTotalsKey, LedgerTotals, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(27)))
+    ));
+}
+
+// Helper function to read the running totals of a ledger account in the given currency
+fn totals_of(currency: &Option<String>, key: (u8, u64)) -> Result<LedgerTotals, Error> {
+    let totals = match currency {
+        None => LEDGER_TOTALS.with(|t| t.borrow().get(&key)),
+        Some(code) => {
+            let code = currency::code_key(code)?;
+            FOREIGN_LEDGER_TOTALS.with(|t| t.borrow().get(&(code, key)))
+        }
+    };
+    Ok(totals.unwrap_or_default())
+}
+
+// Helper function to store the running totals of a ledger account in the given currency
+fn store_totals(currency: &Option<String>, key: (u8, u64), totals: LedgerTotals) {
+    match currency {
+        None => LEDGER_TOTALS.with(|t| t.borrow_mut().insert(key, totals)),
+        Some(code) => {
+            let code =
+                currency::code_key(code).expect("Cannot store totals in an invalid currency");
+            FOREIGN_LEDGER_TOTALS.with(|t| t.borrow_mut().insert((code, key), totals))
+        }
+    };
+}
+
+/// Represents a journal entry whose legs have been checked and whose new totals have been
+/// computed, ready to be posted once its transaction has an ID.
+pub struct PreparedEntry {
+    lines: Vec<JournalLine>,
+    updated: Vec<(Option<String>, (u8, u64), LedgerTotals)>,
+}
+
+/// Checks that the legs of a journal entry balance in every currency and that no ledger
+/// account's totals overflow, without writing anything.
+pub fn prepare_entry(lines: Vec<JournalLine>) -> Result<PreparedEntry, Error> {
+    // Sum both sides per currency and compute the new totals before anything is written
+    let mut sums: Vec<(Option<String>, LedgerTotals)> = Vec::new();
+    let mut updated: Vec<(Option<String>, (u8, u64), LedgerTotals)> = Vec::new();
+    for line in &lines {
+        let key = line.account.key();
+        let mut totals = match updated
+            .iter()
+            .position(|(c, k, _)| *c == line.currency && *k == key)
+        {
+            Some(index) => updated.remove(index).2,
+            None => totals_of(&line.currency, key)?,
+        };
+        let mut sum = match sums.iter().position(|(c, _)| *c == line.currency) {
+            Some(index) => sums.remove(index).1,
+            None => LedgerTotals::default(),
+        };
+        match line.side {
+            EntrySide::Debit => {
+                sum.debits = sum.debits.checked_add(line.amount)?;
+                totals.debits = totals.debits.checked_add(line.amount)?;
+            }
+            EntrySide::Credit => {
+                sum.credits = sum.credits.checked_add(line.amount)?;
+                totals.credits = totals.credits.checked_add(line.amount)?;
+            }
+        }
+        sums.push((line.currency.clone(), sum));
+        updated.push((line.currency.clone(), key, totals));
+    }
+
+    if lines.is_empty() {
+        return Err(Error::UnbalancedEntry {
+            msg: "Journal entry has no lines.".to_string(),
+        });
+    }
+    if let Some((currency, sum)) = sums.iter().find(|(_, sum)| sum.debits != sum.credits) {
+        return Err(Error::UnbalancedEntry {
+            msg: format!(
+                "Journal entry has debits of {} and credits of {}.",
+                currency::format(currency::code_of(currency), sum.debits),
+                currency::format(currency::code_of(currency), sum.credits)
+            ),
+        });
+    }
+    Ok(PreparedEntry { lines, updated })
+}
+
+impl PreparedEntry {
+    /// Posts the entry for the given transaction and stores the new totals.
+    pub fn post(self, transaction_id: u64) -> JournalEntry {
+        let entry = JournalEntry {
+            transaction_id,
+            lines: self.lines,
+            timestamp: time(),
+        };
+        JOURNAL.with(|journal| journal.borrow_mut().insert(transaction_id, entry.clone()));
+        for (currency, key, totals) in self.updated {
+            store_totals(&currency, key, totals);
+        }
+        entry
+    }
+}
+
+/// Returns a customer account's balance in the given currency as recorded by the ledger: its
+/// credits minus its debits.
+pub fn customer_balance(currency: &Option<String>, account_id: u64) -> Result<Money, Error> {
+    let totals = totals_of(currency, LedgerAccount::Customer(account_id).key())?;
+    totals.credits.checked_sub(totals.debits)
+}
+
+/// Returns the amount of base currency currently held in customer accounts: everything deposited
+/// through cash-in minus everything withdrawn through cash-out and everything held in escrow.
+pub fn total_supply() -> Result<Money, Error> {
+    let totals = |account: SystemAccount| {
+        LEDGER_TOTALS
+            .with(|t| t.borrow().get(&LedgerAccount::System(account).key()))
+            .unwrap_or_default()
+    };
+    let cash_in = totals(SystemAccount::CashIn);
+    let cash_out = totals(SystemAccount::CashOut);
+    let escrow = totals(SystemAccount::Escrow);
+    cash_in
+        .debits
+        .checked_sub(cash_in.credits)?
+        .checked_sub(cash_out.credits.checked_sub(cash_out.debits)?)?
+        .checked_sub(escrow.credits.checked_sub(escrow.debits)?)
+}
+
+/// Returns the journal entry posted for a transaction, if any.
+pub fn entry_for(transaction_id: u64) -> Option<JournalEntry> {
+    JOURNAL.with(|journal| journal.borrow().get(&transaction_id))
+}
+
+/// Retrieves the journal entry recorded for the specified transaction.
+#[ic_cdk::query(guard = "caller_is_auditor")]
+fn get_journal_entry(transaction_id: u64) -> Result<JournalEntry, Error> {
+    match entry_for(transaction_id) {
+        Some(entry) => Ok(entry),
+        None => Err(Error::NotFound {
+            msg: format!(
+                "Journal entry for transaction id={} not found.",
+                transaction_id
+            ),
+        }),
+    }
+}
+
+/// Retrieves the debit and credit totals of every ledger account in the given currency, or in
+/// the base currency if none is given.
+#[ic_cdk::query(guard = "caller_is_auditor")]
+fn get_trial_balance(currency: Option<String>) -> Result<TrialBalance, Error> {
+    let currency = currency::resolve(&currency)?;
+    let all_totals: Vec<((u8, u64), LedgerTotals)> = match &currency {
+        None => LEDGER_TOTALS.with(|t| t.borrow().iter().collect()),
+        Some(code) => {
+            let code = currency::code_key(code)?;
+            FOREIGN_LEDGER_TOTALS.with(|t| {
+                t.borrow()
+                    .range((code, (0, 0))..=(code, (u8::MAX, u64::MAX)))
+                    .map(|((_, key), totals)| (key, totals))
+                    .collect()
+            })
+        }
+    };
+
+    let mut lines = Vec::new();
+    let mut total_debits = Money::ZERO;
+    let mut total_credits = Money::ZERO;
+    for (key, totals) in all_totals {
+        total_debits = total_debits.checked_add(totals.debits)?;
+        total_credits = total_credits.checked_add(totals.credits)?;
+        lines.push(TrialBalanceLine {
+            account: LedgerAccount::from_key(key),
+            debits: totals.debits,
+            credits: totals.credits,
+        });
+    }
+    Ok(TrialBalance {
+        currency: currency.unwrap_or_else(|| BASE_CURRENCY.to_string()),
+        lines,
+        total_debits,
+        total_credits,
+    })
+}
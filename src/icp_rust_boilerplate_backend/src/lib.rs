@@ -1,43 +1,94 @@
 // Import necessary libraries and modules
 #[macro_use]
 extern crate serde;
-use candid::{Decode, Encode};
-use ic_cdk::api::time;
+use candid::{Nat, Principal};
+use ic_cdk::api::{caller, data_certificate, time};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
-use ic_stable_structures::{BoundedStorable, Cell, DefaultMemoryImpl, StableBTreeMap, Storable};
-use std::{borrow::Cow, cell::RefCell};
+use ic_stable_structures::{Cell, DefaultMemoryImpl, StableBTreeMap};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+mod batch;
+mod certification;
+mod currency;
+mod dedup;
+mod escrow;
+mod fees;
+mod holds;
+mod icrc1;
+mod icrc2;
+mod icrc3;
+mod ledger;
+mod lifecycle;
+mod money;
+mod overdraft;
+mod quarantine;
+mod reversals;
+mod roles;
+mod schedules;
+mod schema;
+mod timers;
+mod validation;
+
+use batch::BatchResult;
+use currency::{CertifiedBalances, Conversion, Currency};
+use escrow::{Escrow, RefundFailure};
+use fees::{FeeSchedule, TransferQuote};
+use holds::Hold;
+use icrc1::{IcrcAccount, MetadataValue, StandardRecord, Subaccount, TransferArg, TransferError};
+use icrc2::{
+    Allowance, AllowanceArgs, ApproveArgs, ApproveError, TransferFromArgs, TransferFromError,
+};
+use icrc3::{
+    ArchiveInfo, DataCertificate, GetArchivesArgs, GetBlocksArgs, GetBlocksResult,
+    SupportedBlockType,
+};
+use lifecycle::AccountStatus;
+use ledger::{JournalEntry, JournalLine, LedgerAccount, SystemAccount, TrialBalance};
+use money::Money;
+use overdraft::{InterestAccrual, OverdraftTerms, OverdrawnAccount};
+use quarantine::{QuarantinedRecord, Stored};
+use reversals::ReversalSummary;
+use roles::{
+    caller_is_admin, caller_is_auditor, caller_is_teller, has_role, is_staff, Role,
+    StorablePrincipal,
+};
+use schedules::{EndCondition, Schedule};
 
 // Define type aliases for clarity
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 type IdCell = Cell<u64, Memory>;
 
-/// Represents a user account with an ID, holder name, balance, and creation timestamp.
-#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+/// Represents a user account with an ID, owner, holder name, balance, creation timestamp and status.
+///
+/// The owner and subaccount together form the account's ICRC-1 address. The balance is only
+/// negative for accounts drawing on an overdraft, see `overdraft`.
+///
+/// `balance` is the ledger balance; `held` is the part of it reserved by outstanding
+/// authorization holds, see `holds`, and the rest is the available balance.
+///
+/// Both are in the base currency. Balances in the other currencies the account holds are kept
+/// in `foreign_balances`, keyed by ISO 4217 code, see `currency`.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
 struct Account {
     id: u64,
+    owner: Principal,
+    subaccount: Subaccount,
     holder_name: String,
-    balance: f64,
+    balance: Money,
     created_at: u64,
+    status: AccountStatus,
+    held: Money,
+    foreign_balances: BTreeMap<String, Money>,
 }
 
-// Implement storage-related traits for the Account struct
-impl Storable for Account {
-    // Convert the Account struct to a byte representation
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
-
-    // Convert a byte representation to an Account struct
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+impl Account {
+    /// Returns the ledger balance less the amount reserved by outstanding holds.
+    fn available_balance(&self) -> Result<Money, Error> {
+        self.balance.checked_sub(self.held)
     }
 }
 
-impl BoundedStorable for Account {
-    const MAX_SIZE: u32 = 1024;
-    const IS_FIXED_SIZE: bool = false;
-}
-
 // Thread-local storage for managing accounts
 thread_local! {
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(
@@ -49,56 +100,207 @@ thread_local! {
             .expect("Cannot create an ID counter")
     );
 
-    static ACCOUNTS: RefCell<StableBTreeMap<u64, Account, Memory>> =
+    // Accounts are stored as versioned records that may fail to decode, see `schema` and `quarantine`
+    static ACCOUNTS: RefCell<StableBTreeMap<u64, Stored<Account>, Memory>> =
         RefCell::new(StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1)))
     ));
-}
 
-/// Represents a financial transaction between two accounts.
-#[derive(candid::CandidType, Serialize, Deserialize, Default, Clone)]
-struct Transaction {
-    sender_id: u64,
-    receiver_id: u64,
-    amount: f64,
-    timestamp: u64,
+    // Index of account IDs by their ICRC-1 address, keyed by (owner, subaccount)
+    static ACCOUNT_ADDRESSES: RefCell<StableBTreeMap<(StorablePrincipal, Subaccount), u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8)))
+    ));
 }
 
-// Implement storage-related traits for the Transaction struct
-impl Storable for Transaction {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
-
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
+/// Represents the kind of movement recorded by a transaction.
+#[derive(candid::CandidType, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+enum TransactionKind {
+    /// Funds moved between two accounts.
+    #[default]
+    Transfer,
+    /// Funds entering the system into an account.
+    Deposit,
+    /// Funds leaving the system from an account.
+    Withdrawal,
+    /// An ICRC-2 allowance granted by the sender; no funds move.
+    Approval,
+    /// Funds moved from the sender into escrow.
+    EscrowHold,
+    /// Escrowed funds paid out to the receiver, the escrow's payee.
+    EscrowRelease,
+    /// Escrowed funds returned to the receiver, the escrow's payer.
+    EscrowRefund,
 }
 
-impl BoundedStorable for Transaction {
-    const MAX_SIZE: u32 = 1024;
-    const IS_FIXED_SIZE: bool = false;
+/// Represents a financial transaction between two accounts, or into or out of one account.
+///
+/// Deposits have no sender and withdrawals have no receiver. The spender is set for
+/// ICRC-2 approvals and for transfers made on the sender's behalf. The fee, if any, is
+/// debited from the sender on top of the amount and credited to the treasury account.
+/// A reversal links back to the transfer it compensates through `reverses`, and transfers
+/// applied together by `transfer_batch` share a `batch_id`.
+///
+/// The amount and fee are in `currency`, which is none for the base currency. A converted
+/// transfer credits the receiver with the amount and currency of its `conversion` instead.
+///
+/// Each transaction is a block of the ICRC-3 log: `from` and `to` keep the ICRC-1 addresses
+/// of the accounts involved, and `phash` is the hash of the preceding block.
+#[derive(candid::CandidType, Serialize, Deserialize, Default, Clone)]
+struct Transaction {
+    id: u64,
+    kind: TransactionKind,
+    sender_id: Option<u64>,
+    receiver_id: Option<u64>,
+    amount: Money,
+    fee: Option<Money>,
+    reference: Option<String>,
+    memo: Option<Vec<u8>>,
+    spender: Option<IcrcAccount>,
+    expires_at: Option<u64>,
+    timestamp: u64,
+    from: Option<IcrcAccount>,
+    to: Option<IcrcAccount>,
+    phash: Option<icrc3::Hash>,
+    reverses: Option<u64>,
+    batch_id: Option<u64>,
+    currency: Option<String>,
+    conversion: Option<Conversion>,
 }
 
 // Thread-local storage for managing transactions
 thread_local! {
-    static TRANSACTIONS: RefCell<StableBTreeMap<u64, Transaction, Memory>> =
+    static TRANSACTION_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))), 0)
+            .expect("Cannot create a transaction ID counter")
+    );
+
+    static TRANSACTIONS: RefCell<StableBTreeMap<u64, Stored<Transaction>, Memory>> =
         RefCell::new(StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2)))
     ));
+
+    // Secondary index of transactions per account, keyed by (account_id, transaction_id)
+    static ACCOUNT_TRANSACTIONS: RefCell<StableBTreeMap<(u64, u64), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4)))
+    ));
+}
+
+/// Maximum number of transactions returned in a single page.
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// Represents the order in which a page of transactions is returned.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Copy)]
+enum Direction {
+    /// Oldest transactions first.
+    Ascending,
+    /// Newest transactions first.
+    Descending,
+}
+
+/// Represents a page of transactions together with the cursor for the next page.
+#[derive(candid::CandidType, Serialize, Deserialize, Default)]
+struct TransactionPage {
+    transactions: Vec<Transaction>,
+    next_cursor: Option<u64>,
+}
+
+/// Represents an account together with the certificate and witness for its balances.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct CertifiedAccount {
+    account: Account,
+    certificate: Option<Vec<u8>>,
+    witness: Vec<u8>,
+}
+
+/// Represents a balance together with the certificate and witness that prove it.
+///
+/// The witness covers the ledger balance in every currency the account holds; the available
+/// balance also depends on outstanding holds and is not certified.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct CertifiedBalance {
+    balance: Money,
+    available_balance: Money,
+    certificate: Option<Vec<u8>>,
+    witness: Vec<u8>,
+}
+
+/// Represents a transaction together with the certificate and witness for its block hash.
+///
+/// Only the most recent transactions are certified; for older ones the witness proves absence.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct CertifiedTransaction {
+    transaction: Transaction,
+    certificate: Option<Vec<u8>>,
+    witness: Vec<u8>,
 }
 
 /// Represents the payload for transferring funds between two accounts.
+///
+/// A retried request that carries the same idempotency key and `created_at_time` as an
+/// earlier one is rejected with `Error::Duplicate` instead of moving the funds again.
+///
+/// Without a currency, the amount is in the base currency. The receiver must hold the currency
+/// the funds arrive in, so funds sent to an account that does not hold their currency must
+/// carry an explicit conversion into one it does.
 #[derive(candid::CandidType, Serialize, Deserialize, Default)]
 struct TransferPayload {
     sender_id: u64,
     receiver_id: u64,
-    amount: f64,
+    amount: Money,
+    idempotency_key: Option<String>,
+    created_at_time: Option<u64>,
+    currency: Option<String>,
+    conversion: Option<Conversion>,
 }
 
 /// Updates the global state to create a new account with the provided details.
 #[ic_cdk::update]
-fn create_account(holder_name: String, initial_balance: f64) -> Option<Account> {
+fn create_account(holder_name: String, initial_balance: Money) -> Result<Account, Error> {
+    // Validate the input before any state is touched
+    validation::validate_holder_name(&holder_name)?;
+    validation::validate_initial_balance(initial_balance)?;
+
+    // Opening balances bring new money into the system, so only tellers may set them
+    if initial_balance > Money::ZERO && !has_role(&caller(), Role::Teller) {
+        return Err(Error::Unauthorized {
+            msg: "Only tellers may open an account with a non-zero balance.".to_string(),
+        });
+    }
+
+    // Create and store the new account
+    let account = do_create_account(caller(), None, holder_name, initial_balance)?;
+
+    // Record the opening balance as an explicit deposit
+    if initial_balance > Money::ZERO {
+        record_transaction(Transaction {
+            kind: TransactionKind::Deposit,
+            receiver_id: Some(account.id),
+            amount: initial_balance,
+            reference: Some("Initial balance".to_string()),
+            ..Default::default()
+        })?;
+    }
+
+    // Return the created account
+    Ok(account)
+}
+
+// Helper function to allocate an ID for a new account and store it under its ICRC-1 address
+//
+// Without an explicit subaccount, the account ID is used, moving on to the next free
+// value if that address was already taken by an account opened through an ICRC-1 transfer.
+// Every way of opening an account goes through here, so this is where the anonymous
+// principal is kept from owning one.
+fn do_create_account(
+    owner: Principal,
+    subaccount: Option<Subaccount>,
+    holder_name: String,
+    balance: Money,
+) -> Result<Account, Error> {
+    ensure_not_anonymous(&owner)?;
+
     // Generate a new unique account ID
     let id = ID_COUNTER
         .with(|counter| {
@@ -107,80 +309,189 @@ fn create_account(holder_name: String, initial_balance: f64) -> Option<Account>
         })
         .expect("Cannot increment ID counter");
 
+    let subaccount = subaccount.unwrap_or_else(|| {
+        (id..)
+            .map(icrc1::subaccount_from_u64)
+            .find(|candidate| find_account_id(&owner, candidate).is_none())
+            .expect("Cannot find a free subaccount")
+    });
+
     // Create a new account with the provided details
     let account = Account {
         id,
+        owner,
+        subaccount,
         holder_name,
-        balance: initial_balance,
+        balance,
         created_at: time(),
+        status: AccountStatus::Active,
+        held: Money::ZERO,
+        foreign_balances: BTreeMap::new(),
     };
 
-    // Insert the new account into the global storage
+    // Insert the new account and its address into the global storage
     do_insert_account(&account);
+    ACCOUNT_ADDRESSES.with(|index| {
+        index
+            .borrow_mut()
+            .insert((StorablePrincipal(owner), subaccount), id)
+    });
+    Ok(account)
+}
 
-    // Return the created account
-    Some(account)
+// Helper function to retrieve an account or report that it does not exist
+fn get_account_or_not_found(id: u64) -> Result<Account, Error> {
+    _get_account(&id).ok_or_else(|| Error::NotFound {
+        msg: format!("Account with id={} not found.", id),
+    })
 }
 
-// Helper function to insert an account into the global storage
+// Helper function to insert an account into the global storage and certify its balance
 fn do_insert_account(account: &Account) {
-    ACCOUNTS.with(|service| service.borrow_mut().insert(account.id, account.clone()));
+    ACCOUNTS.with(|service| {
+        service
+            .borrow_mut()
+            .insert(account.id, Stored::Valid(account.clone()))
+    });
+    certification::certify_account(account);
 }
 
-/// Retrieves the account with the specified ID from the global state.
+// Helper function to look up an account ID by its ICRC-1 address
+fn find_account_id(owner: &Principal, subaccount: &Subaccount) -> Option<u64> {
+    ACCOUNT_ADDRESSES.with(|index| index.borrow().get(&(StorablePrincipal(*owner), *subaccount)))
+}
+
+/// Retrieves the account with the specified ID from the global state, with its certified balance.
 #[ic_cdk::query]
-fn get_account(id: u64) -> Result<Account, Error> {
+fn get_account(id: u64) -> Result<CertifiedAccount, Error> {
     // Attempt to retrieve the account with the specified ID
     match _get_account(&id) {
-        Some(account) => Ok(account),
+        Some(account) => {
+            ensure_can_view(&account)?;
+            Ok(CertifiedAccount {
+                account,
+                certificate: data_certificate(),
+                witness: certification::encode_witness(&certification::balance_witness(id)),
+            })
+        }
         None => Err(Error::NotFound {
             msg: format!("Account with id={} not found.", id),
         }),
     }
 }
 
-// Helper function to retrieve an account from the global storage
+// Helper function to retrieve an account from the global storage, skipping it if corrupt
 fn _get_account(id: &u64) -> Option<Account> {
-    ACCOUNTS.with(|s| s.borrow().get(id))
+    let record = ACCOUNTS.with(|s| s.borrow().get(id))?;
+    quarantine::valid_record(record)
+}
+
+// Helper function to retrieve every decodable account from the global storage
+fn all_accounts() -> Vec<Account> {
+    let records: Vec<(u64, Stored<Account>)> = ACCOUNTS.with(|s| s.borrow().iter().collect());
+    records
+        .into_iter()
+        .filter_map(|(_, record)| quarantine::valid_record(record))
+        .collect()
+}
+
+// Helper function to reject the anonymous principal, which anyone can act as
+fn ensure_not_anonymous(principal: &Principal) -> Result<(), Error> {
+    if *principal == Principal::anonymous() {
+        Err(Error::Unauthorized {
+            msg: "The anonymous principal may not own accounts or move funds.".to_string(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+// Helper function to reject callers that do not own the given account
+fn ensure_owner(account: &Account) -> Result<(), Error> {
+    ensure_owned_by(account, &caller())
+}
+
+// Helper function to reject principals that do not own the given account
+fn ensure_owned_by(account: &Account, principal: &Principal) -> Result<(), Error> {
+    if account.owner == *principal {
+        Ok(())
+    } else {
+        Err(Error::Unauthorized {
+            msg: format!("Caller does not own the account with id={}.", account.id),
+        })
+    }
+}
+
+// Helper function to reject callers that may not read the given account
+fn ensure_can_view(account: &Account) -> Result<(), Error> {
+    if is_staff(&caller()) {
+        Ok(())
+    } else {
+        ensure_owner(account)
+    }
 }
 
 /// Updates the global state to transfer funds between two accounts.
 #[ic_cdk::update]
 fn transfer_funds(payload: TransferPayload) -> Result<Transaction, Error> {
+    execute_transfer(&caller(), &payload)
+}
+
+// Helper function to carry out a transfer on behalf of the given principal
+//
+// Used by `transfer_funds` for the caller, and by scheduled transfers for the schedule's owner.
+fn execute_transfer(owner: &Principal, payload: &TransferPayload) -> Result<Transaction, Error> {
+    // Validate the payload before any state is touched
+    validation::validate_transfer(payload)?;
+
+    // Reject requests outside the deduplication window and retries of executed requests
+    dedup::prune_expired();
+    if let Some(created_at_time) = payload.created_at_time {
+        dedup::check_created_at_time(created_at_time)?;
+    }
+    if let (Some(key), Some(created_at_time)) = (&payload.idempotency_key, payload.created_at_time)
+    {
+        dedup::check_duplicate(owner, key, created_at_time)?;
+    }
+
+    // Work out the currency and the fee under the current schedule
+    let currency = currency::check_transfer(owner, payload)?;
+    let quote = fees::quote_in(&currency, payload.amount)?;
+
     // Retrieve sender and receiver accounts from the global state
     let sender_account_option: Option<Account> = _get_account(&payload.sender_id);
     let receiver_account_option: Option<Account> = _get_account(&payload.receiver_id);
 
     // Match on both account options
     match (sender_account_option, receiver_account_option) {
-        (Some(mut sender_account), Some(mut receiver_account)) => {
-            // Check if the sender has sufficient funds
-            if sender_account.balance >= payload.amount {
-                // Update sender and receiver balances
-                sender_account.balance -= payload.amount;
-                receiver_account.balance += payload.amount;
-
-                // Create a new transaction record
-                let transaction = Transaction {
-                    sender_id: payload.sender_id,
-                    receiver_id: payload.receiver_id,
+        (Some(sender_account), Some(receiver_account)) => {
+            // Only the owner of the sender account may move funds out of it
+            ensure_owned_by(&sender_account, owner)?;
+
+            let transaction = do_transfer(
+                sender_account,
+                receiver_account,
+                Transaction {
                     amount: payload.amount,
-                    timestamp: time(),
-                };
+                    fee: (quote.fee > Money::ZERO).then_some(quote.fee),
+                    currency,
+                    conversion: payload.conversion.clone(),
+                    ..Default::default()
+                },
+            )?;
 
-                // Insert the new transaction and update both accounts
-                do_insert_transaction(&transaction);
-                do_insert_account(&sender_account);
-                do_insert_account(&receiver_account);
-
-                // Return the created transaction
-                Ok(transaction)
-            } else {
-                // Sender has insufficient funds
-                Err(Error::InsufficientFunds {
-                    msg: "Insufficient funds in the sender's account.".to_string(),
-                })
+            // Remember the request so that a retry returns the recorded transaction ID
+            if let (Some(key), Some(created_at_time)) =
+                (&payload.idempotency_key, payload.created_at_time)
+            {
+                dedup::remember(
+                    owner,
+                    &dedup::idempotency_bytes(key),
+                    created_at_time,
+                    transaction.id,
+                );
             }
+            Ok(transaction)
         }
         _ => {
             // Either sender or receiver account not found
@@ -191,18 +502,424 @@ fn transfer_funds(payload: TransferPayload) -> Result<Transaction, Error> {
     }
 }
 
+// Helper function to move funds between two already authorized accounts and record the transaction
+//
+// The amount, fee, currency, conversion, memo and spender are taken from the given
+// transaction details. Fees are only charged in the base currency.
+fn do_transfer(
+    mut sender_account: Account,
+    mut receiver_account: Account,
+    details: Transaction,
+) -> Result<Transaction, Error> {
+    let amount = details.amount;
+    let fee = details.fee.unwrap_or(Money::ZERO);
+    let total = amount.checked_add(fee)?;
+    let sent = currency::code_of(&details.currency).to_string();
+    let (received, received_amount) = match &details.conversion {
+        Some(conversion) => (conversion.currency.clone(), conversion.amount),
+        None => (sent.clone(), amount),
+    };
+
+    // Only accounts in the right lifecycle state may take part
+    lifecycle::ensure_can_send(&sender_account)?;
+    lifecycle::ensure_can_receive(&receiver_account)?;
+    let treasury = if fee > Money::ZERO {
+        Some(fees::treasury_account()?)
+    } else {
+        None
+    };
+
+    // Check if the sender's funds and overdraft cover both the amount and the fee
+    if currency::spendable_in(&sender_account, &sent)? < total {
+        return Err(Error::InsufficientFunds {
+            msg: "Insufficient funds in the sender's account.".to_string(),
+        });
+    }
+
+    // Update sender and receiver balances and credit the fee to the treasury, which may be one
+    // of them, failing on overflow or on a currency the receiver does not hold instead of
+    // corrupting them
+    currency::debit(&mut sender_account, &sent, total)?;
+    currency::credit(&mut receiver_account, &received, received_amount)?;
+    let treasury = match treasury {
+        Some(treasury) if treasury.id == sender_account.id => {
+            currency::credit(&mut sender_account, &sent, fee)?;
+            None
+        }
+        Some(treasury) if treasury.id == receiver_account.id => {
+            currency::credit(&mut receiver_account, &sent, fee)?;
+            None
+        }
+        Some(mut treasury) => {
+            currency::credit(&mut treasury, &sent, fee)?;
+            Some(treasury)
+        }
+        None => None,
+    };
+
+    // Record the transfer, then store every account it changed
+    let transaction = record_transaction(Transaction {
+        kind: TransactionKind::Transfer,
+        sender_id: Some(sender_account.id),
+        receiver_id: Some(receiver_account.id),
+        ..details
+    })?;
+    do_insert_account(&sender_account);
+    do_insert_account(&receiver_account);
+    if let Some(treasury) = treasury {
+        do_insert_account(&treasury);
+    }
+    Ok(transaction)
+}
+
+/// Updates the global state to credit an account with funds entering the system.
+///
+/// Without a currency, the funds are in the base currency.
+#[ic_cdk::update(guard = "caller_is_teller")]
+fn deposit(
+    account_id: u64,
+    amount: Money,
+    reference: String,
+    currency: Option<String>,
+) -> Result<Transaction, Error> {
+    // Validate the input before any state is touched
+    validation::validate_amount(amount)?;
+    validation::validate_reference(&reference)?;
+    let currency = currency::resolve(&currency)?;
+
+    match _get_account(&account_id) {
+        Some(mut account) => {
+            lifecycle::ensure_can_receive(&account)?;
+            currency::credit(&mut account, currency::code_of(&currency), amount)?;
+            let transaction = record_transaction(Transaction {
+                kind: TransactionKind::Deposit,
+                receiver_id: Some(account_id),
+                amount,
+                reference: Some(reference),
+                currency,
+                ..Default::default()
+            })?;
+            do_insert_account(&account);
+            Ok(transaction)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("Account with id={} not found.", account_id),
+        }),
+    }
+}
+
+/// Updates the global state to debit an account with funds leaving the system.
+///
+/// Without a currency, the funds are in the base currency.
+#[ic_cdk::update(guard = "caller_is_teller")]
+fn withdraw(
+    account_id: u64,
+    amount: Money,
+    reference: String,
+    currency: Option<String>,
+) -> Result<Transaction, Error> {
+    // Validate the input before any state is touched
+    validation::validate_amount(amount)?;
+    validation::validate_reference(&reference)?;
+    let currency = currency::resolve(&currency)?;
+    let code = currency::code_of(&currency);
+
+    match _get_account(&account_id) {
+        Some(mut account) => {
+            lifecycle::ensure_can_send(&account)?;
+            if currency::spendable_in(&account, code)? < amount {
+                return Err(Error::InsufficientFunds {
+                    msg: format!("Insufficient funds in account with id={}.", account_id),
+                });
+            }
+            currency::debit(&mut account, code, amount)?;
+            let transaction = record_transaction(Transaction {
+                kind: TransactionKind::Withdrawal,
+                sender_id: Some(account_id),
+                amount,
+                reference: Some(reference),
+                currency: currency.clone(),
+                ..Default::default()
+            })?;
+            do_insert_account(&account);
+            Ok(transaction)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("Account with id={} not found.", account_id),
+        }),
+    }
+}
+
+// Helper function to timestamp a new transaction, append it to the log and post it to the ledger
+//
+// The journal entry is checked before anything is written, so an error leaves both the log and
+// the ledger untouched.
+fn record_transaction(mut transaction: Transaction) -> Result<Transaction, Error> {
+    transaction.timestamp = time();
+
+    // Approvals do not move funds, so there is nothing to post to the ledger
+    if transaction.kind == TransactionKind::Approval {
+        return append_transaction(transaction);
+    }
+
+    let treasury_id = match transaction.fee {
+        Some(fee) if fee > Money::ZERO => Some(fees::treasury_account()?.id),
+        _ => None,
+    };
+    let entry = ledger::prepare_entry(journal_lines(&transaction, treasury_id)?)?;
+
+    let transaction = append_transaction(transaction)?;
+    entry.post(transaction.id);
+    Ok(transaction)
+}
+
+// Helper function to build the balancing journal entry of a transaction that moves funds, with
+// its fee paid on to the given treasury account
+//
+// Deposits come from cash-in and withdrawals go to cash-out, while escrow movements go through
+// the escrow account.
+fn journal_lines(
+    transaction: &Transaction,
+    treasury_id: Option<u64>,
+) -> Result<Vec<JournalLine>, Error> {
+    let amount = transaction.amount;
+    let currency = transaction.currency.clone();
+    let (source, sink) = match transaction.kind {
+        TransactionKind::EscrowHold
+        | TransactionKind::EscrowRelease
+        | TransactionKind::EscrowRefund => (SystemAccount::Escrow, SystemAccount::Escrow),
+        _ => (SystemAccount::CashIn, SystemAccount::CashOut),
+    };
+    let debit = transaction
+        .sender_id
+        .map_or(LedgerAccount::System(source), LedgerAccount::Customer);
+    let credit = transaction
+        .receiver_id
+        .map_or(LedgerAccount::System(sink), LedgerAccount::Customer);
+    let mut lines = vec![JournalLine::debit(debit, amount).in_currency(currency.clone())];
+
+    // A converted amount goes through the exchange account, which balances in each currency
+    match &transaction.conversion {
+        Some(conversion) => {
+            let target = currency::resolve(&Some(conversion.currency.clone()))?;
+            let exchange = LedgerAccount::System(SystemAccount::Exchange);
+            lines.push(JournalLine::credit(exchange, amount).in_currency(currency.clone()));
+            lines.push(JournalLine::debit(exchange, conversion.amount).in_currency(target.clone()));
+            lines.push(JournalLine::credit(credit, conversion.amount).in_currency(target));
+        }
+        None => lines.push(JournalLine::credit(credit, amount).in_currency(currency.clone())),
+    }
+
+    // The fee is a second debit to the sender, earned as fee income and then paid on to the
+    // treasury account that holds it
+    if let Some(fee) = transaction.fee.filter(|fee| *fee > Money::ZERO) {
+        let treasury_id = treasury_id.ok_or_else(|| Error::InvalidFeeSchedule {
+            msg: "No treasury account is configured.".to_string(),
+        })?;
+        let fee_income = LedgerAccount::System(SystemAccount::FeeIncome);
+        let treasury = LedgerAccount::Customer(treasury_id);
+        lines.push(JournalLine::debit(debit, fee).in_currency(currency.clone()));
+        lines.push(JournalLine::credit(fee_income, fee).in_currency(currency.clone()));
+        lines.push(JournalLine::debit(fee_income, fee).in_currency(currency.clone()));
+        lines.push(JournalLine::credit(treasury, fee).in_currency(currency));
+    }
+    Ok(lines)
+}
+
+// Helper function to assign an ID to a transaction, then store and return it
+//
+// The transaction is chained to the previous one and becomes the new certified tip of the log.
+fn append_transaction(mut transaction: Transaction) -> Result<Transaction, Error> {
+    let phash = icrc3::chain_hash()?;
+    let address = |id: Option<u64>| {
+        id.and_then(|id| _get_account(&id))
+            .map(|account| IcrcAccount {
+                owner: account.owner,
+                subaccount: Some(account.subaccount),
+            })
+    };
+    transaction.from = address(transaction.sender_id);
+    transaction.to = address(transaction.receiver_id);
+    transaction.phash = phash;
+    transaction.id = next_transaction_id();
+    do_insert_transaction(&transaction);
+    let hash = icrc3::hash_value(&icrc3::transaction_to_block(&transaction));
+    icrc3::set_tip(transaction.id, hash);
+    certification::certify_transaction(transaction.id, hash);
+    Ok(transaction)
+}
+
+// Helper function to generate the next unique transaction ID
+fn next_transaction_id() -> u64 {
+    TRANSACTION_ID_COUNTER
+        .with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter.borrow_mut().set(current_value + 1)
+        })
+        .expect("Cannot increment transaction ID counter")
+}
+
+// Helper function to return the number of transaction IDs handed out so far
+fn transaction_count() -> u64 {
+    TRANSACTION_ID_COUNTER.with(|counter| *counter.borrow().get())
+}
+
+// Helper function to retrieve a transaction from the global storage, skipping it if corrupt
+fn _get_transaction(id: &u64) -> Option<Transaction> {
+    let record = TRANSACTIONS.with(|s| s.borrow().get(id))?;
+    quarantine::valid_record(record)
+}
+
+// Helper function to retrieve every decodable transaction with an ID in the given range
+fn transactions_in(range: impl std::ops::RangeBounds<u64>) -> Vec<Transaction> {
+    let records: Vec<(u64, Stored<Transaction>)> =
+        TRANSACTIONS.with(|s| s.borrow().range(range).collect());
+    records
+        .into_iter()
+        .filter_map(|(_, record)| quarantine::valid_record(record))
+        .collect()
+}
+
 // Helper function to insert a transaction into the global storage
 fn do_insert_transaction(transaction: &Transaction) {
-    TRANSACTIONS.with(|service| service.borrow_mut().insert(transaction.timestamp, transaction.clone()));
+    TRANSACTIONS.with(|service| {
+        service
+            .borrow_mut()
+            .insert(transaction.id, Stored::Valid(transaction.clone()))
+    });
+
+    // Index the transaction under the sender and the receiver account, where present
+    ACCOUNT_TRANSACTIONS.with(|index| {
+        let mut index = index.borrow_mut();
+        for account_id in [transaction.sender_id, transaction.receiver_id]
+            .into_iter()
+            .flatten()
+        {
+            index.insert((account_id, transaction.id), ());
+        }
+    });
 }
 
-/// Retrieves all transactions from the global state.
+/// Retrieves the transaction with the specified ID from the global state, with its certificate.
+#[ic_cdk::query]
+fn get_transaction(id: u64) -> Result<CertifiedTransaction, Error> {
+    // Attempt to retrieve the transaction with the specified ID
+    match _get_transaction(&id) {
+        Some(transaction) => {
+            ensure_can_view_transaction(&transaction)?;
+            Ok(CertifiedTransaction {
+                transaction,
+                certificate: data_certificate(),
+                witness: certification::encode_witness(&certification::transaction_witness(id)),
+            })
+        }
+        None => Err(Error::NotFound {
+            msg: format!("Transaction with id={} not found.", id),
+        }),
+    }
+}
+
+// Helper function to reject callers other than staff and the owners of the involved accounts
+fn ensure_can_view_transaction(transaction: &Transaction) -> Result<(), Error> {
+    let caller = caller();
+    let involved = [transaction.sender_id, transaction.receiver_id]
+        .iter()
+        .flatten()
+        .filter_map(_get_account)
+        .any(|account| account.owner == caller);
+    if involved || is_staff(&caller) {
+        Ok(())
+    } else {
+        Err(Error::Unauthorized {
+            msg: format!(
+                "Caller may not view the transaction with id={}.",
+                transaction.id
+            ),
+        })
+    }
+}
+
+/// Retrieves a page of transactions sent or received by the specified account.
+///
+/// `cursor` is the ID of the last transaction of the previous page; pass `None` to start
+/// from the oldest (ascending) or newest (descending) transaction.
 #[ic_cdk::query]
+fn get_account_transactions(
+    account_id: u64,
+    cursor: Option<u64>,
+    limit: u32,
+    direction: Direction,
+) -> Result<TransactionPage, Error> {
+    // Make sure the account exists and is visible to the caller before reading its history
+    match _get_account(&account_id) {
+        Some(account) => ensure_can_view(&account)?,
+        None => {
+            return Err(Error::NotFound {
+                msg: format!("Account with id={} not found.", account_id),
+            })
+        }
+    }
+
+    // Fetch one extra ID to find out whether another page follows
+    let limit = limit.clamp(1, MAX_PAGE_SIZE) as usize;
+    let ids: Vec<u64> = ACCOUNT_TRANSACTIONS.with(|index| {
+        let index = index.borrow();
+        match direction {
+            Direction::Ascending => {
+                let start = match cursor {
+                    Some(cursor) => match cursor.checked_add(1) {
+                        Some(start) => start,
+                        None => return Vec::new(),
+                    },
+                    None => 0,
+                };
+                index
+                    .range((account_id, start)..=(account_id, u64::MAX))
+                    .take(limit + 1)
+                    .map(|((_, tx_id), _)| tx_id)
+                    .collect()
+            }
+            Direction::Descending => {
+                // The index cannot be iterated backwards, so step down one key at a time
+                let mut ids = Vec::new();
+                let mut bound = (account_id, cursor.unwrap_or(u64::MAX));
+                while ids.len() <= limit {
+                    match index.iter_upper_bound(&bound).next() {
+                        Some(((owner, tx_id), _)) if owner == account_id => {
+                            ids.push(tx_id);
+                            bound = (account_id, tx_id);
+                        }
+                        _ => break,
+                    }
+                }
+                ids
+            }
+        }
+    });
+
+    let has_more = ids.len() > limit;
+    let transactions: Vec<Transaction> = ids
+        .into_iter()
+        .take(limit)
+        .filter_map(|id| _get_transaction(&id))
+        .collect();
+    let next_cursor = if has_more {
+        transactions.last().map(|transaction| transaction.id)
+    } else {
+        None
+    };
+
+    Ok(TransactionPage {
+        transactions,
+        next_cursor,
+    })
+}
+
+/// Retrieves all transactions from the global state.
+#[ic_cdk::query(guard = "caller_is_auditor")]
 fn get_all_transactions() -> Result<Vec<Transaction>, Error> {
-    // Retrieve all transactions and convert them to a Vec
-    let transactions_map: Vec<(u64, Transaction)> =
-        TRANSACTIONS.with(|service| service.borrow().iter().collect());
-    let transactions: Vec<Transaction> = transactions_map.into_iter().map(|(_, txn)| txn).collect();
+    // Retrieve all transactions, skipping any that cannot be decoded
+    let transactions = transactions_in(..);
 
     // Check if any transactions were found
     if !transactions.is_empty() {
@@ -221,7 +938,10 @@ fn get_all_transactions() -> Result<Vec<Transaction>, Error> {
 fn get_sender_account(sender_id: u64) -> Result<Account, Error> {
     // Attempt to retrieve the sender account with the specified ID
     match _get_account(&sender_id) {
-        Some(account) => Ok(account),
+        Some(account) => {
+            ensure_can_view(&account)?;
+            Ok(account)
+        }
         None => Err(Error::NotFound {
             msg: format!("Sender account with id={} not found.", sender_id),
         }),
@@ -233,19 +953,30 @@ fn get_sender_account(sender_id: u64) -> Result<Account, Error> {
 fn get_receiver_account(receiver_id: u64) -> Result<Account, Error> {
     // Attempt to retrieve the receiver account with the specified ID
     match _get_account(&receiver_id) {
-        Some(account) => Ok(account),
+        Some(account) => {
+            ensure_can_view(&account)?;
+            Ok(account)
+        }
         None => Err(Error::NotFound {
             msg: format!("Receiver account with id={} not found.", receiver_id),
         }),
     }
 }
 
-/// Retrieves the balance of the account with the specified ID from the global state.
+/// Retrieves the certified balance of the account with the specified ID from the global state.
 #[ic_cdk::query]
-fn get_account_balance(id: u64) -> Result<f64, Error> {
+fn get_account_balance(id: u64) -> Result<CertifiedBalance, Error> {
     // Attempt to retrieve the account with the specified ID and return its balance
     match _get_account(&id) {
-        Some(account) => Ok(account.balance),
+        Some(account) => {
+            ensure_can_view(&account)?;
+            Ok(CertifiedBalance {
+                balance: account.balance,
+                available_balance: account.available_balance()?,
+                certificate: data_certificate(),
+                witness: certification::encode_witness(&certification::balance_witness(id)),
+            })
+        }
         None => Err(Error::NotFound {
             msg: format!("Account with id={} not found.", id),
         }),
@@ -257,7 +988,10 @@ fn get_account_balance(id: u64) -> Result<f64, Error> {
 fn get_account_created_at(id: u64) -> Result<u64, Error> {
     // Attempt to retrieve the account with the specified ID and return its creation timestamp
     match _get_account(&id) {
-        Some(account) => Ok(account.created_at),
+        Some(account) => {
+            ensure_can_view(&account)?;
+            Ok(account.created_at)
+        }
         None => Err(Error::NotFound {
             msg: format!("Account with id={} not found.", id),
         }),
@@ -265,21 +999,23 @@ fn get_account_created_at(id: u64) -> Result<u64, Error> {
 }
 
 /// Retrieves all accounts from the global state.
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "caller_is_auditor")]
 fn get_all_accounts() -> Vec<Account> {
-    // Retrieve all accounts and convert them to a Vec
-    let accounts_map: Vec<(u64, Account)> =
-        ACCOUNTS.with(|service| service.borrow().iter().collect());
-    let accounts: Vec<Account> = accounts_map.into_iter().map(|(_, acc)| acc).collect();
-    accounts
+    // Retrieve all accounts, skipping any that cannot be decoded
+    all_accounts()
 }
 
 /// Updates the global state to update the holder name of the account with the specified ID.
 #[ic_cdk::update]
 fn update_account_holder_name(id: u64, new_holder_name: String) -> Result<(), Error> {
+    // Validate the new name before any state is touched
+    validation::validate_holder_name(&new_holder_name)?;
+
     // Attempt to retrieve the account with the specified ID
     match _get_account(&id) {
         Some(mut account) => {
+            ensure_owner(&account)?;
+
             // Update the holder name and insert the modified account back into the global state
             account.holder_name = new_holder_name;
             do_insert_account(&account);
@@ -294,13 +1030,58 @@ fn update_account_holder_name(id: u64, new_holder_name: String) -> Result<(), Er
     }
 }
 
-/// Updates the global state to delete the account with the specified ID.
+/// Hands an account migrated from the original layout over to its rightful owner.
+///
+/// Accounts created before ownership existed belong to the canister itself; only those can
+/// be reassigned, and the account keeps its subaccount under the new owner.
+#[ic_cdk::update(guard = "caller_is_admin")]
+fn assign_account_owner(id: u64, owner: Principal) -> Result<Account, Error> {
+    let mut account = get_account_or_not_found(id)?;
+    if account.owner != ic_cdk::id() {
+        return Err(Error::InvalidOwner {
+            msg: format!("Account with id={} already has an owner.", id),
+        });
+    }
+    ensure_not_anonymous(&owner)?;
+    if owner == ic_cdk::id() {
+        return Err(Error::InvalidOwner {
+            msg: format!("Principal {} cannot own an account.", owner),
+        });
+    }
+    if let Some(other) = find_account_id(&owner, &account.subaccount) {
+        return Err(Error::InvalidOwner {
+            msg: format!(
+                "The new owner already uses that address for account id={}.",
+                other
+            ),
+        });
+    }
+
+    // Move the account's address over to the new owner along with the account
+    ACCOUNT_ADDRESSES.with(|index| {
+        let mut index = index.borrow_mut();
+        index.remove(&(StorablePrincipal(account.owner), account.subaccount));
+        index.insert((StorablePrincipal(owner), account.subaccount), id);
+    });
+    account.owner = owner;
+    do_insert_account(&account);
+    Ok(account)
+}
+
+/// Updates the global state to close the account with the specified ID.
+///
+/// Only accounts with a zero balance can be closed; use `close_account` to sweep a remaining
+/// balance first. The account is kept in the `Closed` state so that its transactions still
+/// refer to it.
 #[ic_cdk::update]
 fn delete_account(id: u64) -> Result<(), Error> {
     // Check if the account with the specified ID exists
-    if let Some(_account) = _get_account(&id) {
-        // Remove the account from the global state
-        ACCOUNTS.with(|service| service.borrow_mut().remove(&id));
+    if let Some(mut account) = _get_account(&id) {
+        ensure_owner(&account)?;
+
+        // Close the account and store it back, keeping its address reserved
+        lifecycle::transition(&mut account, AccountStatus::Closed)?;
+        do_insert_account(&account);
         Ok(())
     } else {
         // Account not found
@@ -310,13 +1091,153 @@ fn delete_account(id: u64) -> Result<(), Error> {
     }
 }
 
+/// Updates the global state to close an account, sweeping its remaining balances to a
+/// beneficiary.
+///
+/// Returns the final transfer in each currency the account still held, starting with the base
+/// currency; none if the account was already empty. The beneficiary must hold every one of
+/// those currencies, otherwise nothing is swept and the error names the ones it lacks.
+#[ic_cdk::update]
+fn close_account(id: u64, beneficiary_id: u64) -> Result<Vec<Transaction>, Error> {
+    if id == beneficiary_id {
+        return Err(Error::SelfTransfer {
+            msg: format!("Cannot sweep account with id={} into itself.", id),
+        });
+    }
+
+    match (_get_account(&id), _get_account(&beneficiary_id)) {
+        (Some(account), Some(beneficiary)) => {
+            ensure_owner(&account)?;
+            if account.balance < Money::ZERO {
+                return Err(Error::BalanceNotZero {
+                    msg: format!(
+                        "Account with id={} is overdrawn by {} and cannot be closed.",
+                        id,
+                        Money::ZERO.checked_sub(account.balance)?
+                    ),
+                });
+            }
+
+            // Sweep every remaining balance and check that the emptied account may be closed
+            // before anything is stored; the account does not have to be able to send
+            let mut closed = account;
+            let mut beneficiary = beneficiary;
+            let remaining = lifecycle::sweep(&mut closed, &mut beneficiary)?;
+            lifecycle::transition(&mut closed, AccountStatus::Closed)?;
+
+            // Record the sweep in each currency as the account's final transactions
+            let sweeps = remaining
+                .into_iter()
+                .map(|(code, amount)| {
+                    record_transaction(Transaction {
+                        kind: TransactionKind::Transfer,
+                        sender_id: Some(closed.id),
+                        receiver_id: Some(beneficiary.id),
+                        amount,
+                        reference: Some("Account closure".to_string()),
+                        currency: Some(code).filter(|code| code != currency::BASE_CURRENCY),
+                        ..Default::default()
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            do_insert_account(&closed);
+            if !sweeps.is_empty() {
+                do_insert_account(&beneficiary);
+            }
+            Ok(sweeps)
+        }
+        _ => Err(Error::NotFound {
+            msg: "Account or beneficiary account not found.".to_string(),
+        }),
+    }
+}
+
+/// Marks the stable memory of a fresh install as using the current schema version.
+#[ic_cdk::init]
+fn init() {
+    schema::record_current_version();
+}
+
+/// Records the schema version of the stable memory before the code is replaced.
+#[ic_cdk::pre_upgrade]
+fn pre_upgrade() {
+    schema::record_current_version();
+}
+
+/// Migrates the stable memory to the current schema version, then rebuilds the certified
+/// tree and sets the timers for scheduled transfers, escrow deadlines and hold expiries, none
+/// of which survives an upgrade.
+#[ic_cdk::post_upgrade]
+fn post_upgrade() {
+    schema::migrate();
+    quarantine::quarantine_corrupt_records();
+    certification::rebuild();
+    schedules::rearm();
+    escrow::rearm();
+    holds::rearm();
+}
+
 /// Represents possible errors that can occur during account operations.
-#[derive(candid::CandidType, Deserialize, Serialize)]
+#[derive(candid::CandidType, Deserialize, Serialize, Clone, Debug)]
 enum Error {
     /// Indicates that the requested resource was not found.
     NotFound { msg: String },
     /// Indicates that there are insufficient funds for a particular operation.
     InsufficientFunds { msg: String },
+    /// Indicates that a monetary calculation would overflow.
+    ArithmeticOverflow { msg: String },
+    /// Indicates that the caller is not allowed to perform the operation.
+    Unauthorized { msg: String },
+    /// Indicates that an amount is zero, negative or otherwise not acceptable.
+    InvalidAmount { msg: String },
+    /// Indicates that a transfer names the same account as sender and receiver.
+    SelfTransfer { msg: String },
+    /// Indicates that a holder name is empty or blank.
+    InvalidName { msg: String },
+    /// Indicates that a holder name exceeds the maximum allowed length.
+    NameTooLong { msg: String },
+    /// Indicates that a transaction reference is not acceptable.
+    InvalidReference { msg: String },
+    /// Indicates that the debit and credit legs of a journal entry do not balance.
+    UnbalancedEntry { msg: String },
+    /// Indicates that an account's status does not allow the operation.
+    AccountNotActive { msg: String },
+    /// Indicates that an account cannot move from its current status to the requested one.
+    InvalidStatusTransition { msg: String },
+    /// Indicates that an account still holds funds and cannot be closed.
+    BalanceNotZero { msg: String },
+    /// Indicates that a replacement for a quarantined record is not acceptable.
+    InvalidRepair { msg: String },
+    /// Indicates that an idempotency key is empty, too long or missing its creation time.
+    InvalidIdempotencyKey { msg: String },
+    /// Indicates that a request's creation time lies outside the deduplication window.
+    InvalidCreatedAtTime { msg: String },
+    /// Indicates that a fee schedule is inconsistent or cannot be applied.
+    InvalidFeeSchedule { msg: String },
+    /// Indicates that an overdraft limit or interest rate is not acceptable.
+    InvalidOverdraftTerms { msg: String },
+    /// Indicates that a scheduled transfer is not acceptable or can no longer be changed.
+    InvalidSchedule { msg: String },
+    /// Indicates that an escrow is not acceptable or cannot be settled as requested.
+    InvalidEscrow { msg: String },
+    /// Indicates that a hold is not acceptable or can no longer be captured or released.
+    InvalidHold { msg: String },
+    /// Indicates that a transaction cannot be reversed, or not by the requested amount.
+    InvalidReversal { msg: String },
+    /// Indicates that a currency code is malformed, not supported or cannot be registered.
+    InvalidCurrency { msg: String },
+    /// Indicates that funds arrive in a currency the receiving account does not hold.
+    CurrencyMismatch { msg: String },
+    /// Indicates that a batch of transfers is empty, too large or contains an unacceptable leg.
+    InvalidBatch { msg: String },
+    /// Indicates that a valid leg was not applied because another leg of its batch failed.
+    BatchAborted { msg: String },
+    /// Indicates that the request was already executed as the given transaction.
+    Duplicate { existing_tx_id: u64 },
+    /// Indicates that a principal cannot own the account.
+    InvalidOwner { msg: String },
+    /// Indicates that the block log cannot grow until its last block is repaired.
+    TipUnavailable { msg: String },
 }
 
 // Export Candid interface for the defined functions and types
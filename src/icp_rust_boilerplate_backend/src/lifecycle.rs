@@ -0,0 +1,297 @@
+use crate::currency::{self, BASE_CURRENCY};
+use crate::escrow;
+use crate::money::Money;
+use crate::roles::caller_is_teller;
+use crate::{_get_account, do_insert_account, Account, Error};
+
+/// Represents the lifecycle state of an account.
+#[derive(
+    candid::CandidType, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug,
+)]
+pub enum AccountStatus {
+    /// The account can send and receive funds.
+    #[default]
+    Active,
+    /// The account is blocked by staff and can neither send nor receive funds.
+    Frozen,
+    /// The account has been inactive; it can receive funds but must be reactivated to send.
+    Dormant,
+    /// The account is closed for good; it keeps its history but cannot move funds.
+    Closed,
+}
+
+impl AccountStatus {
+    // Returns true if an account may move from this status to the given one
+    fn can_become(self, next: AccountStatus) -> bool {
+        use AccountStatus::*;
+        matches!(
+            (self, next),
+            (Active, Frozen)
+                | (Active, Dormant)
+                | (Active, Closed)
+                | (Frozen, Active)
+                | (Dormant, Active)
+                | (Dormant, Frozen)
+                | (Dormant, Closed)
+        )
+    }
+}
+
+/// Rejects accounts that may not send funds.
+pub fn ensure_can_send(account: &Account) -> Result<(), Error> {
+    if account.status == AccountStatus::Active {
+        Ok(())
+    } else {
+        Err(Error::AccountNotActive {
+            msg: format!(
+                "Account with id={} is {:?} and cannot send funds.",
+                account.id, account.status
+            ),
+        })
+    }
+}
+
+/// Rejects accounts that may not receive funds.
+pub fn ensure_can_receive(account: &Account) -> Result<(), Error> {
+    match account.status {
+        AccountStatus::Active | AccountStatus::Dormant => Ok(()),
+        status => Err(Error::AccountNotActive {
+            msg: format!(
+                "Account with id={} is {:?} and cannot receive funds.",
+                account.id, status
+            ),
+        }),
+    }
+}
+
+/// Moves an account to a new status, rejecting transitions the lifecycle does not allow.
+///
+/// Only accounts with a zero balance in every currency, no outstanding holds and no held
+/// escrows can be closed.
+pub fn transition(account: &mut Account, next: AccountStatus) -> Result<(), Error> {
+    if !account.status.can_become(next) {
+        return Err(Error::InvalidStatusTransition {
+            msg: format!(
+                "Account with id={} cannot go from {:?} to {:?}.",
+                account.id, account.status, next
+            ),
+        });
+    }
+    if next == AccountStatus::Closed && account.balance != Money::ZERO {
+        return Err(Error::BalanceNotZero {
+            msg: format!(
+                "Account with id={} still holds {} and cannot be closed.",
+                account.id, account.balance
+            ),
+        });
+    }
+    if next == AccountStatus::Closed {
+        let mut foreign = account.foreign_balances.iter();
+        if let Some((code, balance)) = foreign.find(|(_, balance)| **balance != Money::ZERO) {
+            return Err(Error::BalanceNotZero {
+                msg: format!(
+                    "Account with id={} still holds {} and cannot be closed.",
+                    account.id,
+                    currency::format(code, *balance)
+                ),
+            });
+        }
+    }
+    if next == AccountStatus::Closed && account.held != Money::ZERO {
+        return Err(Error::BalanceNotZero {
+            msg: format!(
+                "Account with id={} has {} on hold and cannot be closed.",
+                account.id, account.held
+            ),
+        });
+    }
+    if next == AccountStatus::Closed && escrow::has_held_escrows(account.id) {
+        return Err(Error::BalanceNotZero {
+            msg: format!(
+                "Account with id={} is party to a held escrow and cannot be closed.",
+                account.id
+            ),
+        });
+    }
+    account.status = next;
+    Ok(())
+}
+
+/// Moves an account's remaining balances to the beneficiary of its closure, returning the
+/// amount swept in each currency, starting with the base currency.
+///
+/// Unlike a transfer, this does not require the account to be able to send, so that a
+/// dormant account can still be emptied and closed; the beneficiary must be able to receive
+/// and must hold every currency there is something left in. Nothing moves unless every
+/// balance can.
+pub fn sweep(
+    account: &mut Account,
+    beneficiary: &mut Account,
+) -> Result<Vec<(String, Money)>, Error> {
+    let mut remaining = vec![(BASE_CURRENCY.to_string(), account.balance)];
+    remaining.extend(account.foreign_balances.clone());
+    remaining.retain(|(_, amount)| *amount != Money::ZERO);
+    if remaining.is_empty() {
+        return Ok(remaining);
+    }
+    ensure_can_receive(beneficiary)?;
+    let blocking: Vec<&str> = remaining
+        .iter()
+        .filter(|(code, _)| currency::balance_in(beneficiary, code).is_none())
+        .map(|(code, _)| code.as_str())
+        .collect();
+    if !blocking.is_empty() {
+        return Err(Error::CurrencyMismatch {
+            msg: format!(
+                "Account with id={} does not hold {}, which account with id={} still holds.",
+                beneficiary.id,
+                blocking.join(", "),
+                account.id
+            ),
+        });
+    }
+
+    // Credit a copy of the beneficiary so that an overflow leaves both accounts untouched
+    let mut credited = beneficiary.clone();
+    for (code, amount) in &remaining {
+        currency::credit(&mut credited, code, *amount)?;
+    }
+    *beneficiary = credited;
+    account.balance = Money::ZERO;
+    account
+        .foreign_balances
+        .values_mut()
+        .for_each(|balance| *balance = Money::ZERO);
+    Ok(remaining)
+}
+
+// Helper function to load an account, apply a status transition and store it again
+fn set_status(id: u64, next: AccountStatus) -> Result<Account, Error> {
+    match _get_account(&id) {
+        Some(mut account) => {
+            transition(&mut account, next)?;
+            do_insert_account(&account);
+            Ok(account)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("Account with id={} not found.", id),
+        }),
+    }
+}
+
+/// Freezes an account so that it can neither send nor receive funds.
+#[ic_cdk::update(guard = "caller_is_teller")]
+fn freeze_account(id: u64) -> Result<Account, Error> {
+    set_status(id, AccountStatus::Frozen)
+}
+
+/// Lifts a freeze from an account.
+#[ic_cdk::update(guard = "caller_is_teller")]
+fn unfreeze_account(id: u64) -> Result<Account, Error> {
+    match _get_account(&id) {
+        Some(account) if account.status != AccountStatus::Frozen => {
+            Err(Error::InvalidStatusTransition {
+                msg: format!("Account with id={} is not frozen.", id),
+            })
+        }
+        _ => set_status(id, AccountStatus::Active),
+    }
+}
+
+/// Marks an inactive account as dormant, blocking outgoing funds until it is reactivated.
+#[ic_cdk::update(guard = "caller_is_teller")]
+fn mark_account_dormant(id: u64) -> Result<Account, Error> {
+    set_status(id, AccountStatus::Dormant)
+}
+
+/// Reactivates a dormant account.
+#[ic_cdk::update(guard = "caller_is_teller")]
+fn reactivate_account(id: u64) -> Result<Account, Error> {
+    match _get_account(&id) {
+        Some(account) if account.status != AccountStatus::Dormant => {
+            Err(Error::InvalidStatusTransition {
+                msg: format!("Account with id={} is not dormant.", id),
+            })
+        }
+        _ => set_status(id, AccountStatus::Active),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::Principal;
+
+    fn account(id: u64, balance: i64, status: AccountStatus) -> Account {
+        Account {
+            id,
+            owner: Principal::management_canister(),
+            subaccount: [0; 32],
+            holder_name: format!("Holder {}", id),
+            balance: Money::from_minor_units(balance),
+            created_at: 0,
+            status,
+            held: Money::ZERO,
+            foreign_balances: Default::default(),
+        }
+    }
+
+    #[test]
+    fn dormant_account_with_a_balance_can_be_swept_and_closed() {
+        let mut dormant = account(1, 1_250, AccountStatus::Dormant);
+        let mut beneficiary = account(2, 100, AccountStatus::Active);
+
+        assert_eq!(
+            sweep(&mut dormant, &mut beneficiary).unwrap(),
+            vec![(BASE_CURRENCY.to_string(), Money::from_minor_units(1_250))]
+        );
+        assert_eq!(dormant.balance, Money::ZERO);
+        assert_eq!(beneficiary.balance, Money::from_minor_units(1_350));
+        assert!(transition(&mut dormant, AccountStatus::Closed).is_ok());
+        assert_eq!(dormant.status, AccountStatus::Closed);
+    }
+
+    #[test]
+    fn sweep_requires_a_beneficiary_that_can_receive() {
+        let mut dormant = account(1, 1_250, AccountStatus::Dormant);
+        let mut beneficiary = account(2, 100, AccountStatus::Frozen);
+
+        assert!(sweep(&mut dormant, &mut beneficiary).is_err());
+        assert_eq!(dormant.balance, Money::from_minor_units(1_250));
+        assert_eq!(beneficiary.balance, Money::from_minor_units(100));
+    }
+
+    #[test]
+    fn sweep_moves_every_currency_or_names_the_ones_missing() {
+        let mut closing = account(1, 500, AccountStatus::Active);
+        closing
+            .foreign_balances
+            .insert("EUR".to_string(), Money::from_minor_units(70));
+        closing
+            .foreign_balances
+            .insert("USD".to_string(), Money::ZERO);
+        let mut beneficiary = account(2, 0, AccountStatus::Active);
+
+        let error = sweep(&mut closing, &mut beneficiary).unwrap_err();
+        assert!(matches!(error, Error::CurrencyMismatch { ref msg } if msg.contains("EUR")));
+        assert_eq!(closing.balance, Money::from_minor_units(500));
+        assert_eq!(beneficiary.balance, Money::ZERO);
+
+        beneficiary
+            .foreign_balances
+            .insert("EUR".to_string(), Money::from_minor_units(5));
+        assert_eq!(
+            sweep(&mut closing, &mut beneficiary).unwrap(),
+            vec![
+                (BASE_CURRENCY.to_string(), Money::from_minor_units(500)),
+                ("EUR".to_string(), Money::from_minor_units(70)),
+            ]
+        );
+        assert_eq!(beneficiary.balance, Money::from_minor_units(500));
+        assert_eq!(
+            beneficiary.foreign_balances["EUR"],
+            Money::from_minor_units(75)
+        );
+        assert!(transition(&mut closing, AccountStatus::Closed).is_ok());
+    }
+}
@@ -0,0 +1,95 @@
+use crate::Error;
+use std::fmt;
+
+/// Represents an exact monetary amount stored as an integer number of minor units (e.g. cents).
+#[derive(
+    candid::CandidType,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+pub struct Money(i64);
+
+impl Money {
+    /// Number of decimal places between the minor and the major unit.
+    pub const DECIMALS: u32 = 2;
+
+    /// The zero amount.
+    pub const ZERO: Money = Money(0);
+
+    /// Creates an amount from a number of minor units.
+    pub const fn from_minor_units(units: i64) -> Self {
+        Money(units)
+    }
+
+    /// Returns the amount as a number of minor units.
+    pub const fn minor_units(self) -> i64 {
+        self.0
+    }
+
+    /// Adds two amounts, returning an error instead of wrapping on overflow.
+    pub fn checked_add(self, other: Money) -> Result<Money, Error> {
+        self.0
+            .checked_add(other.0)
+            .map(Money)
+            .ok_or_else(|| Error::ArithmeticOverflow {
+                msg: format!("Overflow while adding {} and {}.", self, other),
+            })
+    }
+
+    /// Subtracts two amounts, returning an error instead of wrapping on overflow.
+    pub fn checked_sub(self, other: Money) -> Result<Money, Error> {
+        self.0
+            .checked_sub(other.0)
+            .map(Money)
+            .ok_or_else(|| Error::ArithmeticOverflow {
+                msg: format!("Overflow while subtracting {} from {}.", other, self),
+            })
+    }
+
+    /// Formats the amount in major units of a currency with the given number of decimal
+    /// places, e.g. `-12.05` with 2 or `-0.1205` with 4.
+    pub fn format_with(self, decimals: u8) -> String {
+        let scale = 10u64.pow(u32::from(decimals));
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        match decimals {
+            0 => format!("{}{}", sign, abs),
+            _ => format!(
+                "{}{}.{:0width$}",
+                sign,
+                abs / scale,
+                abs % scale,
+                width = usize::from(decimals)
+            ),
+        }
+    }
+}
+
+// Format the amount in major units of the base currency, e.g. `-12.05`
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format_with(Self::DECIMALS as u8))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts_are_formatted_with_the_given_decimals() {
+        let amount = Money::from_minor_units(-120_005);
+        assert_eq!(amount.to_string(), "-1200.05");
+        assert_eq!(amount.format_with(0), "-120005");
+        assert_eq!(amount.format_with(4), "-12.0005");
+        assert_eq!(Money::from_minor_units(7).format_with(8), "0.00000007");
+    }
+}
@@ -0,0 +1,274 @@
+use crate::fees::{self, BASIS_POINTS_PER_UNIT};
+use crate::money::Money;
+use crate::roles::{caller_is_auditor, caller_is_teller};
+use crate::{
+    _get_account, do_insert_account, ensure_can_view, record_transaction, Account, Error, Memory,
+    Transaction, TransactionKind, MEMORY_MANAGER,
+};
+use candid::{Decode, Encode};
+use ic_cdk::api::time;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+/// Number of nanoseconds in the 365-day year used for interest accrual.
+pub const NANOS_PER_YEAR: u64 = 365 * 24 * 60 * 60 * 1_000_000_000;
+
+/// Represents the overdraft facility of an account.
+///
+/// The balance may go as far below zero as `limit`. While it is negative, interest accrues at
+/// `annual_rate_bps` basis points a year and is charged to the account in favour of the treasury.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Default)]
+pub struct OverdraftTerms {
+    pub limit: Money,
+    pub annual_rate_bps: u32,
+    pub last_accrued_at: u64,
+}
+
+impl Storable for OverdraftTerms {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for OverdraftTerms {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Represents an account whose balance is currently below zero.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct OverdrawnAccount {
+    pub account_id: u64,
+    pub holder_name: String,
+    pub balance: Money,
+    pub limit: Money,
+    pub annual_rate_bps: u32,
+}
+
+/// Represents an account whose overdraft interest could not be charged.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct AccrualFailure {
+    pub account_id: u64,
+    pub error: Error,
+}
+
+/// Represents the outcome of charging overdraft interest across every account.
+///
+/// A failure on one account does not stop the others from being charged.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct InterestAccrual {
+    pub charges: Vec<Transaction>,
+    pub failures: Vec<AccrualFailure>,
+}
+
+// Thread-local storage for overdraft facilities, keyed by account ID
+thread_local! {
+    static OVERDRAFTS: RefCell<StableBTreeMap<u64, OverdraftTerms, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14)))
+    ));
+}
+
+/// Returns the overdraft facility of an account; accounts without one have a zero limit.
+pub fn terms(account_id: u64) -> OverdraftTerms {
+    OVERDRAFTS
+        .with(|o| o.borrow().get(&account_id))
+        .unwrap_or_default()
+}
+
+/// Returns how much the account may still send: its available balance plus its overdraft limit.
+pub fn spendable(account: &Account) -> Result<Money, Error> {
+    account
+        .available_balance()?
+        .checked_add(terms(account.id).limit)
+}
+
+// Helper function to compute the interest owed on a negative balance over the elapsed time
+//
+// The result is rounded down to whole minor units.
+fn interest_on(balance: Money, annual_rate_bps: u32, elapsed: u64) -> Result<Money, Error> {
+    if balance >= Money::ZERO {
+        return Ok(Money::ZERO);
+    }
+    let overflow = || Error::ArithmeticOverflow {
+        msg: format!(
+            "Overflow while computing interest on {} at {} basis points.",
+            balance, annual_rate_bps
+        ),
+    };
+    let owed = i128::from(balance.minor_units())
+        .unsigned_abs()
+        .checked_mul(u128::from(annual_rate_bps))
+        .and_then(|units| units.checked_mul(u128::from(elapsed)))
+        .ok_or_else(overflow)?
+        / (u128::from(BASIS_POINTS_PER_UNIT) * u128::from(NANOS_PER_YEAR));
+    i64::try_from(owed)
+        .map(Money::from_minor_units)
+        .map_err(|_| overflow())
+}
+
+// Helper function to charge the interest accrued on one account since its last accrual
+//
+// Interest is charged on the balance at the time of accrual, so accrual is meant to run at
+// regular intervals. While nothing is owed the accrual time simply moves forward; a charge
+// that rounds down to zero leaves it in place so that the time is not lost. Without a
+// treasury to collect it, interest is left to accrue until one is configured.
+fn accrue(
+    account_id: u64,
+    mut terms: OverdraftTerms,
+    now: u64,
+    treasury_id: Option<u64>,
+) -> Result<Option<Transaction>, Error> {
+    let mut account = match _get_account(&account_id) {
+        Some(account) => account,
+        None => return Ok(None),
+    };
+    let elapsed = now.saturating_sub(terms.last_accrued_at);
+    let interest = interest_on(account.balance, terms.annual_rate_bps, elapsed)?;
+    if account.balance < Money::ZERO && interest == Money::ZERO {
+        return Ok(None);
+    }
+
+    let mut charge = None;
+    if interest > Money::ZERO {
+        let treasury_id = match treasury_id {
+            Some(treasury_id) => treasury_id,
+            None => return Ok(None),
+        };
+        if treasury_id != account_id {
+            let mut treasury = _get_account(&treasury_id).ok_or_else(|| Error::NotFound {
+                msg: format!("Treasury account with id={} not found.", treasury_id),
+            })?;
+            account.balance = account.balance.checked_sub(interest)?;
+            treasury.balance = treasury.balance.checked_add(interest)?;
+            charge = Some(record_transaction(Transaction {
+                kind: TransactionKind::Transfer,
+                sender_id: Some(account_id),
+                receiver_id: Some(treasury_id),
+                amount: interest,
+                reference: Some("Overdraft interest".to_string()),
+                ..Default::default()
+            })?);
+            do_insert_account(&account);
+            do_insert_account(&treasury);
+        }
+    }
+
+    terms.last_accrued_at = now;
+    OVERDRAFTS.with(|o| o.borrow_mut().insert(account_id, terms));
+    Ok(charge)
+}
+
+// Helper function to look up the treasury that collects interest, if one is configured,
+// checking that it can still receive funds before any interest is charged
+fn treasury_id() -> Result<Option<u64>, Error> {
+    match fees::schedule().treasury_account_id {
+        Some(_) => Ok(Some(fees::treasury_account()?.id)),
+        None => Ok(None),
+    }
+}
+
+/// Sets the overdraft limit and interest rate of an account.
+///
+/// Interest owed under the previous terms is charged first, or waived if no treasury is
+/// configured to collect it; a configured treasury that cannot receive funds blocks the
+/// change. A lower limit does not affect a balance that is already below
+/// it, but blocks further spending until the account recovers.
+#[ic_cdk::update(guard = "caller_is_teller")]
+fn set_overdraft_terms(
+    account_id: u64,
+    limit: Money,
+    annual_rate_bps: u32,
+) -> Result<OverdraftTerms, Error> {
+    if limit < Money::ZERO {
+        return Err(Error::InvalidOverdraftTerms {
+            msg: format!("Overdraft limit must not be negative, got {}.", limit),
+        });
+    }
+    if annual_rate_bps > BASIS_POINTS_PER_UNIT {
+        return Err(Error::InvalidOverdraftTerms {
+            msg: format!(
+                "Interest rate of {} basis points exceeds the maximum of {}.",
+                annual_rate_bps, BASIS_POINTS_PER_UNIT
+            ),
+        });
+    }
+    if _get_account(&account_id).is_none() {
+        return Err(Error::NotFound {
+            msg: format!("Account with id={} not found.", account_id),
+        });
+    }
+
+    let now = time();
+    if let Some(previous) = OVERDRAFTS.with(|o| o.borrow().get(&account_id)) {
+        accrue(account_id, previous, now, treasury_id()?)?;
+    }
+    let terms = OverdraftTerms {
+        limit,
+        annual_rate_bps,
+        last_accrued_at: now,
+    };
+    OVERDRAFTS.with(|o| o.borrow_mut().insert(account_id, terms.clone()));
+    Ok(terms)
+}
+
+/// Retrieves the overdraft limit and interest rate of an account.
+#[ic_cdk::query]
+fn get_overdraft_terms(account_id: u64) -> Result<OverdraftTerms, Error> {
+    match _get_account(&account_id) {
+        Some(account) => {
+            ensure_can_view(&account)?;
+            Ok(terms(account_id))
+        }
+        None => Err(Error::NotFound {
+            msg: format!("Account with id={} not found.", account_id),
+        }),
+    }
+}
+
+/// Charges the interest accrued on every overdrawn account since the previous accrual.
+///
+/// Returns the interest transactions that were recorded and the accounts that could not be
+/// charged. Nothing is charged if the configured treasury cannot receive funds.
+#[ic_cdk::update(guard = "caller_is_teller")]
+fn accrue_overdraft_interest() -> Result<InterestAccrual, Error> {
+    let now = time();
+    let treasury_id = treasury_id()?;
+    let facilities: Vec<(u64, OverdraftTerms)> = OVERDRAFTS.with(|o| o.borrow().iter().collect());
+    let mut accrual = InterestAccrual {
+        charges: Vec::new(),
+        failures: Vec::new(),
+    };
+    for (account_id, terms) in facilities {
+        match accrue(account_id, terms, now, treasury_id) {
+            Ok(Some(charge)) => accrual.charges.push(charge),
+            Ok(None) => {}
+            Err(error) => accrual.failures.push(AccrualFailure { account_id, error }),
+        }
+    }
+    Ok(accrual)
+}
+
+/// Retrieves every account whose balance is currently below zero.
+#[ic_cdk::query(guard = "caller_is_auditor")]
+fn get_overdrawn_accounts() -> Vec<OverdrawnAccount> {
+    let facilities: Vec<(u64, OverdraftTerms)> = OVERDRAFTS.with(|o| o.borrow().iter().collect());
+    facilities
+        .into_iter()
+        .filter_map(|(account_id, terms)| {
+            let account = _get_account(&account_id)?;
+            (account.balance < Money::ZERO).then_some(OverdrawnAccount {
+                account_id,
+                holder_name: account.holder_name,
+                balance: account.balance,
+                limit: terms.limit,
+                annual_rate_bps: terms.annual_rate_bps,
+            })
+        })
+        .collect()
+}
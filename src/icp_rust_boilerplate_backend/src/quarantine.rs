@@ -0,0 +1,339 @@
+use crate::ledger::{JournalLine, LedgerAccount};
+use crate::money::Money;
+use crate::roles::caller_is_admin;
+use crate::schema::VersionedRecord;
+use crate::{
+    _get_transaction, certification, currency, do_insert_account, do_insert_transaction,
+    find_account_id, holds, icrc3, journal_lines, ledger, validation, Account, Error, Memory,
+    StorablePrincipal, Transaction, TransactionKind, ACCOUNTS, ACCOUNT_ADDRESSES, MEMORY_MANAGER,
+    TRANSACTIONS,
+};
+use candid::{Decode, Encode};
+use ic_cdk::api::time;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+/// Represents a record as read from stable memory, which may have failed to decode.
+pub enum Stored<T> {
+    Valid(T),
+    Corrupt { bytes: Vec<u8>, error: String },
+}
+
+// Decoding never traps; records that cannot be decoded are kept as raw bytes
+impl<T: VersionedRecord> Storable for Stored<T> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        match self {
+            Stored::Valid(record) => Cow::Owned(record.encode_record()),
+            Stored::Corrupt { bytes, .. } => Cow::Borrowed(bytes),
+        }
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match T::decode_record(bytes.as_ref()) {
+            Ok(record) => Stored::Valid(record),
+            Err(error) => Stored::Corrupt {
+                bytes: bytes.into_owned(),
+                error,
+            },
+        }
+    }
+}
+
+impl<T: VersionedRecord> BoundedStorable for Stored<T> {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Represents the kind of record that was quarantined.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    Account,
+    Transaction,
+}
+
+impl RecordKind {
+    // Encode the record kind as part of a stable map key
+    fn tag(self) -> u8 {
+        match self {
+            RecordKind::Account => 0,
+            RecordKind::Transaction => 1,
+        }
+    }
+}
+
+/// Represents a record that could not be decoded, together with the reason.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct QuarantinedRecord {
+    pub kind: RecordKind,
+    pub key: u64,
+    pub bytes: Vec<u8>,
+    pub error: String,
+    pub quarantined_at: u64,
+}
+
+impl Storable for QuarantinedRecord {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for QuarantinedRecord {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Thread-local storage for quarantined records, keyed by (record kind, original key)
+thread_local! {
+    static QUARANTINE: RefCell<StableBTreeMap<(u8, u64), QuarantinedRecord, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12)))
+    ));
+}
+
+/// Returns a valid record, or none for a corrupt one, which is left where it is.
+///
+/// Reads never move records, since queries reach this path and may not update the certified
+/// data; corrupt records are moved by `quarantine_corrupt_records` instead.
+pub fn valid_record<T>(record: Stored<T>) -> Option<T> {
+    match record {
+        Stored::Valid(record) => Some(record),
+        Stored::Corrupt { .. } => None,
+    }
+}
+
+// Helper function to move a corrupt record out of its map and into quarantine
+fn quarantine(kind: RecordKind, key: u64, bytes: Vec<u8>, error: String) {
+    match kind {
+        RecordKind::Account => {
+            ACCOUNTS.with(|s| s.borrow_mut().remove(&key));
+            certification::uncertify_account(key);
+        }
+        RecordKind::Transaction => {
+            TRANSACTIONS.with(|s| s.borrow_mut().remove(&key));
+        }
+    }
+    let record = QuarantinedRecord {
+        kind,
+        key,
+        bytes,
+        error,
+        quarantined_at: time(),
+    };
+    QUARANTINE.with(|q| q.borrow_mut().insert((kind.tag(), key), record));
+}
+
+/// Decodes every stored account and transaction, moving those that are corrupt into quarantine.
+///
+/// Only call this from updates, timers or upgrades, as it updates the certified data. Returns
+/// the number of records that were quarantined.
+pub fn quarantine_corrupt_records() -> u64 {
+    let mut quarantined = 0;
+    let accounts: Vec<(u64, Stored<Account>)> = ACCOUNTS.with(|s| s.borrow().iter().collect());
+    for (key, record) in accounts {
+        if let Stored::Corrupt { bytes, error } = record {
+            quarantine(RecordKind::Account, key, bytes, error);
+            quarantined += 1;
+        }
+    }
+    let transactions: Vec<(u64, Stored<Transaction>)> =
+        TRANSACTIONS.with(|s| s.borrow().iter().collect());
+    for (key, record) in transactions {
+        if let Stored::Corrupt { bytes, error } = record {
+            quarantine(RecordKind::Transaction, key, bytes, error);
+            quarantined += 1;
+        }
+    }
+    quarantined
+}
+
+// Helper function to remove a quarantined record once it has been repaired
+fn take_quarantined(kind: RecordKind, key: u64) -> Result<QuarantinedRecord, Error> {
+    QUARANTINE
+        .with(|q| q.borrow_mut().remove(&(kind.tag(), key)))
+        .ok_or_else(|| Error::NotFound {
+            msg: format!("No quarantined record with key={}.", key),
+        })
+}
+
+// Helper function to reject a repair whose key does not match the quarantined record
+fn ensure_key_matches(key: u64, id: u64) -> Result<(), Error> {
+    if key == id {
+        Ok(())
+    } else {
+        Err(Error::InvalidRepair {
+            msg: format!(
+                "Record id={} does not match the quarantined key={}.",
+                id, key
+            ),
+        })
+    }
+}
+
+// Helper function to reject a repaired transaction that differs from the journal entry posted
+// for it
+//
+// The fee leg names the treasury at the time of posting, so the treasury is taken from the
+// entry rather than from the current fee schedule.
+fn ensure_journaled(transaction: &Transaction) -> Result<(), Error> {
+    let entry = ledger::entry_for(transaction.id);
+    let matches = match (&transaction.kind, entry) {
+        (TransactionKind::Approval, entry) => entry.is_none(),
+        (_, Some(entry)) => {
+            let treasury_id = match entry.lines.last() {
+                Some(JournalLine {
+                    account: LedgerAccount::Customer(id),
+                    ..
+                }) => Some(*id),
+                _ => None,
+            };
+            journal_lines(transaction, treasury_id).is_ok_and(|lines| lines == entry.lines)
+        }
+        (_, None) => false,
+    };
+    if matches {
+        Ok(())
+    } else {
+        Err(Error::InvalidRepair {
+            msg: format!(
+                "Record id={} does not match the journal entry posted for it.",
+                transaction.id
+            ),
+        })
+    }
+}
+
+// Helper function to reject a repaired balance that differs from the one recorded by the ledger
+fn ensure_booked(key: u64, currency: Option<String>, balance: Money) -> Result<(), Error> {
+    let booked = ledger::customer_balance(&currency, key)?;
+    if balance == booked {
+        Ok(())
+    } else {
+        Err(Error::InvalidRepair {
+            msg: format!(
+                "Balance {} {} of account id={} differs from the {} recorded by the ledger.",
+                balance,
+                currency::code_of(&currency),
+                key,
+                booked
+            ),
+        })
+    }
+}
+
+/// Decodes every stored account and transaction, quarantining those that are corrupt.
+///
+/// Returns the number of records that were quarantined.
+#[ic_cdk::update(guard = "caller_is_admin")]
+fn scan_for_corrupt_records() -> u64 {
+    quarantine_corrupt_records()
+}
+
+/// Retrieves all quarantined records.
+#[ic_cdk::query(guard = "caller_is_admin")]
+fn get_quarantined_records() -> Vec<QuarantinedRecord> {
+    QUARANTINE.with(|q| q.borrow().iter().map(|(_, record)| record).collect())
+}
+
+/// Replaces a quarantined account with a corrected record supplied by an admin.
+///
+/// The repaired balances must match the ledger in every currency, and the amount held must
+/// match the account's active holds, so that a repair cannot move money off the books.
+#[ic_cdk::update(guard = "caller_is_admin")]
+fn repair_account(key: u64, account: Account) -> Result<(), Error> {
+    // Check the replacement before the quarantined record is released
+    ensure_key_matches(key, account.id)?;
+    validation::validate_holder_name(&account.holder_name)?;
+    ensure_booked(key, None, account.balance)?;
+    for code in account.foreign_balances.keys() {
+        currency::get(code)?;
+    }
+    for registered in currency::registered() {
+        let balance = currency::balance_in(&account, &registered.code).unwrap_or(Money::ZERO);
+        ensure_booked(key, Some(registered.code), balance)?;
+    }
+    let held = holds::active_total(key)?;
+    if account.held != held {
+        return Err(Error::InvalidRepair {
+            msg: format!(
+                "Account id={} has {} on hold, not {}.",
+                key, held, account.held
+            ),
+        });
+    }
+    if let Some(other) = find_account_id(&account.owner, &account.subaccount) {
+        if other != key {
+            return Err(Error::InvalidRepair {
+                msg: format!(
+                    "The address of account id={} is now used by account id={}.",
+                    key, other
+                ),
+            });
+        }
+    }
+    take_quarantined(RecordKind::Account, key)?;
+
+    do_insert_account(&account);
+    ACCOUNT_ADDRESSES.with(|index| {
+        index.borrow_mut().insert(
+            (StorablePrincipal(account.owner), account.subaccount),
+            account.id,
+        )
+    });
+    Ok(())
+}
+
+/// Replaces a quarantined transaction with a corrected record supplied by an admin.
+///
+/// The repaired block must link to the block before it, and the block after it must link to
+/// the repaired block, so that a repair cannot break the hash chain. A repaired last block
+/// must match the stored tip of the log, or becomes the tip if none was stored. The repair
+/// must also agree with the journal entry posted for the transaction.
+#[ic_cdk::update(guard = "caller_is_admin")]
+fn repair_transaction(key: u64, transaction: Transaction) -> Result<(), Error> {
+    ensure_key_matches(key, transaction.id)?;
+    let expected_phash = match key.checked_sub(1) {
+        Some(previous) => {
+            let previous = _get_transaction(&previous).ok_or_else(|| Error::InvalidRepair {
+                msg: format!(
+                    "The block before id={} is missing and must be repaired first.",
+                    key
+                ),
+            })?;
+            Some(icrc3::hash_value(&icrc3::transaction_to_block(&previous)))
+        }
+        None => None,
+    };
+    if transaction.phash != expected_phash {
+        return Err(Error::InvalidRepair {
+            msg: format!("Record id={} does not link to the block before it.", key),
+        });
+    }
+    let hash = icrc3::hash_value(&icrc3::transaction_to_block(&transaction));
+    if let Some(next) = key.checked_add(1).and_then(|next| _get_transaction(&next)) {
+        if next.phash != Some(hash) {
+            return Err(Error::InvalidRepair {
+                msg: format!("The block after id={} does not link to the repair.", key),
+            });
+        }
+    }
+    ensure_journaled(&transaction)?;
+    if let Some((_, tip_hash)) = icrc3::tip().filter(|(tip_id, _)| *tip_id == key) {
+        if tip_hash != hash {
+            return Err(Error::InvalidRepair {
+                msg: format!("Record id={} does not match the tip of the log.", key),
+            });
+        }
+    }
+    take_quarantined(RecordKind::Transaction, key)?;
+
+    // The repaired block may hash differently, so the certified tree is rebuilt
+    do_insert_transaction(&transaction);
+    icrc3::store_tip_of_log();
+    certification::rebuild();
+    Ok(())
+}
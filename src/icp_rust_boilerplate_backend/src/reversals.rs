@@ -0,0 +1,179 @@
+use crate::money::Money;
+use crate::roles::{has_role, Role};
+use crate::{
+    _get_account, _get_transaction, do_transfer, ensure_can_view_transaction, validation, Error,
+    Memory, Transaction, TransactionKind, MEMORY_MANAGER,
+};
+use candid::{Decode, Encode};
+use ic_cdk::api::caller;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+/// Maximum number of partial reversals of a single transaction.
+pub const MAX_REVERSALS_PER_TRANSACTION: usize = 16;
+
+/// Represents the reversals recorded against a transaction so far.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Default)]
+struct Reversals {
+    reversed: Money,
+    reversal_ids: Vec<u64>,
+}
+
+impl Storable for Reversals {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Reversals {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Represents how much of a transfer has been reversed and how much can still be refunded.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+pub struct ReversalSummary {
+    pub transaction_id: u64,
+    pub original_amount: Money,
+    pub reversed: Money,
+    pub refundable: Money,
+    pub reversal_ids: Vec<u64>,
+}
+
+// Thread-local storage for reversals, keyed by the ID of the reversed transaction
+thread_local! {
+    static REVERSALS: RefCell<StableBTreeMap<u64, Reversals, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(24)))
+    ));
+}
+
+// Helper function to retrieve a transfer that may be reversed, with its sender and receiver IDs
+fn get_reversible(tx_id: u64) -> Result<(Transaction, u64, u64), Error> {
+    let original = _get_transaction(&tx_id).ok_or_else(|| Error::NotFound {
+        msg: format!("Transaction with id={} not found.", tx_id),
+    })?;
+    let parties = (original.sender_id, original.receiver_id);
+    let (sender_id, receiver_id) = match parties {
+        (Some(sender_id), Some(receiver_id)) if original.kind == TransactionKind::Transfer => {
+            (sender_id, receiver_id)
+        }
+        _ => {
+            return Err(Error::InvalidReversal {
+                msg: format!("Transaction with id={} is not a transfer.", tx_id),
+            })
+        }
+    };
+    if original.reverses.is_some() {
+        return Err(Error::InvalidReversal {
+            msg: format!("Transaction with id={} is itself a reversal.", tx_id),
+        });
+    }
+    if original.conversion.is_some() {
+        return Err(Error::InvalidReversal {
+            msg: format!(
+                "Transaction with id={} was converted between currencies.",
+                tx_id
+            ),
+        });
+    }
+    Ok((original, sender_id, receiver_id))
+}
+
+// Helper function to summarize the reversals of a transfer
+fn summarize(original: &Transaction, reversals: Reversals) -> Result<ReversalSummary, Error> {
+    Ok(ReversalSummary {
+        transaction_id: original.id,
+        original_amount: original.amount,
+        refundable: original.amount.checked_sub(reversals.reversed)?,
+        reversed: reversals.reversed,
+        reversal_ids: reversals.reversal_ids,
+    })
+}
+
+/// Sends all or part of a transfer back from its receiver to its sender, recording a
+/// transaction linked to the original.
+///
+/// Only the owner of the receiving account or a teller may reverse a transfer. The total
+/// reversed can never exceed the original amount, and is refunded in the original currency;
+/// fees charged on the original are not refunded. Converted transfers cannot be reversed.
+#[ic_cdk::update]
+fn reverse_transaction(tx_id: u64, amount: Money, reason: String) -> Result<Transaction, Error> {
+    // Validate the request before any state is touched
+    validation::validate_amount(amount)?;
+    if reason.trim().is_empty() {
+        return Err(Error::InvalidReversal {
+            msg: "A reason is required to reverse a transaction.".to_string(),
+        });
+    }
+    validation::validate_reference(&reason)?;
+
+    let (original, sender_id, receiver_id) = get_reversible(tx_id)?;
+    let (payer, payee) = match (_get_account(&receiver_id), _get_account(&sender_id)) {
+        (Some(payer), Some(payee)) => (payer, payee),
+        _ => {
+            return Err(Error::NotFound {
+                msg: "Sender or receiver account not found.".to_string(),
+            })
+        }
+    };
+    let caller = caller();
+    if payer.owner != caller && !has_role(&caller, Role::Teller) {
+        return Err(Error::Unauthorized {
+            msg: format!("Caller may not reverse the transaction with id={}.", tx_id),
+        });
+    }
+
+    let mut reversals = REVERSALS
+        .with(|r| r.borrow().get(&tx_id))
+        .unwrap_or_default();
+    if reversals.reversal_ids.len() >= MAX_REVERSALS_PER_TRANSACTION {
+        return Err(Error::InvalidReversal {
+            msg: format!(
+                "Transaction with id={} has already been reversed {} times.",
+                tx_id, MAX_REVERSALS_PER_TRANSACTION
+            ),
+        });
+    }
+    let refundable = original.amount.checked_sub(reversals.reversed)?;
+    if amount > refundable {
+        return Err(Error::InvalidReversal {
+            msg: format!(
+                "Only {} of transaction with id={} can still be refunded, got {}.",
+                refundable, tx_id, amount
+            ),
+        });
+    }
+
+    let reversal = do_transfer(
+        payer,
+        payee,
+        Transaction {
+            amount,
+            reference: Some(reason),
+            reverses: Some(tx_id),
+            currency: original.currency.clone(),
+            ..Default::default()
+        },
+    )?;
+    reversals.reversed = reversals.reversed.checked_add(amount)?;
+    reversals.reversal_ids.push(reversal.id);
+    REVERSALS.with(|r| r.borrow_mut().insert(tx_id, reversals));
+    Ok(reversal)
+}
+
+/// Retrieves how much of a transfer has been reversed and how much can still be refunded.
+#[ic_cdk::query]
+fn get_reversals(tx_id: u64) -> Result<ReversalSummary, Error> {
+    let (original, _, _) = get_reversible(tx_id)?;
+    ensure_can_view_transaction(&original)?;
+    let reversals = REVERSALS
+        .with(|r| r.borrow().get(&tx_id))
+        .unwrap_or_default();
+    summarize(&original, reversals)
+}
@@ -0,0 +1,172 @@
+use crate::{Error, Memory, MEMORY_MANAGER};
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::{caller, is_controller};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+/// Represents an operational role that can be granted to a principal.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Manages roles and canister configuration.
+    Admin,
+    /// Posts deposits and withdrawals on behalf of customers.
+    Teller,
+    /// Has read-only access to all accounts and transactions.
+    Auditor,
+}
+
+/// Wraps a `Principal` so that it can be used as a stable map key.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StorablePrincipal(pub Principal);
+
+// Default to the anonymous principal so the wrapper can be part of a tuple key
+impl Default for StorablePrincipal {
+    fn default() -> Self {
+        Self(Principal::anonymous())
+    }
+}
+
+impl Storable for StorablePrincipal {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0.as_slice())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(Principal::from_slice(bytes.as_ref()))
+    }
+}
+
+impl BoundedStorable for StorablePrincipal {
+    const MAX_SIZE: u32 = 29;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Represents the set of roles held by a single principal.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct RoleSet {
+    roles: Vec<Role>,
+}
+
+impl Storable for RoleSet {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for RoleSet {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Thread-local storage for the role registry
+thread_local! {
+    static ROLES: RefCell<StableBTreeMap<StorablePrincipal, RoleSet, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5)))
+    ));
+}
+
+/// Returns true if the principal holds the given role.
+///
+/// Canister controllers are always treated as admins so that the first roles can be granted.
+pub fn has_role(principal: &Principal, role: Role) -> bool {
+    if role == Role::Admin && is_controller(principal) {
+        return true;
+    }
+    ROLES.with(|roles| {
+        roles
+            .borrow()
+            .get(&StorablePrincipal(*principal))
+            .is_some_and(|set| set.roles.contains(&role))
+    })
+}
+
+/// Returns true if the principal is an admin, teller or auditor.
+pub fn is_staff(principal: &Principal) -> bool {
+    [Role::Admin, Role::Teller, Role::Auditor]
+        .iter()
+        .any(|role| has_role(principal, *role))
+}
+
+/// Guard that only lets admins through.
+pub fn caller_is_admin() -> Result<(), String> {
+    if has_role(&caller(), Role::Admin) {
+        Ok(())
+    } else {
+        Err("Caller is not an admin.".to_string())
+    }
+}
+
+/// Guard that only lets tellers through.
+pub fn caller_is_teller() -> Result<(), String> {
+    if has_role(&caller(), Role::Teller) {
+        Ok(())
+    } else {
+        Err("Caller is not a teller.".to_string())
+    }
+}
+
+/// Guard that only lets admins and auditors through.
+pub fn caller_is_auditor() -> Result<(), String> {
+    let caller = caller();
+    if has_role(&caller, Role::Admin) || has_role(&caller, Role::Auditor) {
+        Ok(())
+    } else {
+        Err("Caller is not an admin or auditor.".to_string())
+    }
+}
+
+/// Grants a role to the specified principal.
+#[ic_cdk::update(guard = "caller_is_admin")]
+fn grant_role(principal: Principal, role: Role) -> Result<(), Error> {
+    ROLES.with(|roles| {
+        let mut roles = roles.borrow_mut();
+        let key = StorablePrincipal(principal);
+        let mut set = roles.get(&key).unwrap_or_default();
+        if !set.roles.contains(&role) {
+            set.roles.push(role);
+        }
+        roles.insert(key, set);
+    });
+    Ok(())
+}
+
+/// Revokes a role from the specified principal.
+#[ic_cdk::update(guard = "caller_is_admin")]
+fn revoke_role(principal: Principal, role: Role) -> Result<(), Error> {
+    ROLES.with(|roles| {
+        let mut roles = roles.borrow_mut();
+        let key = StorablePrincipal(principal);
+        match roles.get(&key) {
+            Some(mut set) if set.roles.contains(&role) => {
+                set.roles.retain(|r| *r != role);
+                if set.roles.is_empty() {
+                    roles.remove(&key);
+                } else {
+                    roles.insert(key, set);
+                }
+                Ok(())
+            }
+            _ => Err(Error::NotFound {
+                msg: format!("Principal {} does not hold the requested role.", principal),
+            }),
+        }
+    })
+}
+
+/// Retrieves the roles held by the specified principal.
+#[ic_cdk::query(guard = "caller_is_auditor")]
+fn get_roles(principal: Principal) -> Vec<Role> {
+    ROLES.with(|roles| {
+        roles
+            .borrow()
+            .get(&StorablePrincipal(principal))
+            .map(|set| set.roles)
+            .unwrap_or_default()
+    })
+}
@@ -0,0 +1,371 @@
+use crate::currency;
+use crate::money::Money;
+use crate::roles::{has_role, is_staff, Role};
+use crate::timers::{self, Queue, TimerSlot};
+use crate::{
+    _get_account, ensure_not_anonymous, ensure_owner, execute_transfer, validation, Error, IdCell,
+    Memory, TransferPayload, MEMORY_MANAGER,
+};
+use candid::{Decode, Encode, Principal};
+use ic_cdk::api::{caller, time};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::{borrow::Cow, cell::RefCell};
+
+/// Shortest interval allowed between two runs of a recurring transfer, in nanoseconds.
+pub const MIN_INTERVAL_NANOS: u64 = 60 * 1_000_000_000;
+
+/// Number of attempts made for each run before it is given up.
+pub const MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before a failed run is attempted again, in nanoseconds.
+pub const RETRY_DELAY_NANOS: u64 = 10 * 60 * 1_000_000_000;
+
+/// Number of most recent failures kept with each schedule.
+pub const MAX_RECORDED_FAILURES: usize = 5;
+
+/// Maximum number of scheduled runs executed by a single timer callback.
+pub const MAX_RUNS_PER_TICK: usize = 50;
+
+/// Represents when a recurring transfer stops.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Copy)]
+pub enum EndCondition {
+    /// The transfer recurs until it is cancelled.
+    Never,
+    /// The transfer stops after the given number of successful runs.
+    AfterExecutions(u64),
+    /// The transfer stops once its next run would fall after the given time.
+    At(u64),
+}
+
+/// Represents the state of a scheduled transfer.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleStatus {
+    /// The transfer is waiting for its next run.
+    Active,
+    /// The transfer has run for the last time.
+    Completed,
+    /// The transfer was cancelled before it completed.
+    Cancelled,
+    /// The transfer cannot run anymore, e.g. because one of its accounts is gone.
+    Failed,
+}
+
+/// Represents a failed attempt to run a scheduled transfer.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct ScheduleFailure {
+    pub attempted_at: u64,
+    pub attempt: u32,
+    pub error: Error,
+}
+
+/// Represents a one-off or recurring transfer executed by the canister on the owner's behalf.
+///
+/// `due_at` is the time the current run is due; `next_run_at` is when it is next attempted,
+/// which is later than `due_at` while a failed run is being retried. The amount is in
+/// `currency`, which is none for the base currency.
+#[derive(candid::CandidType, Serialize, Deserialize, Clone)]
+pub struct Schedule {
+    pub id: u64,
+    pub owner: Principal,
+    pub sender_id: u64,
+    pub receiver_id: u64,
+    pub amount: Money,
+    pub currency: Option<String>,
+    pub interval: Option<u64>,
+    pub end_condition: EndCondition,
+    pub status: ScheduleStatus,
+    pub due_at: u64,
+    pub next_run_at: u64,
+    pub attempt: u32,
+    pub executions: u64,
+    pub last_transaction_id: Option<u64>,
+    pub failures: Vec<ScheduleFailure>,
+    pub created_at: u64,
+}
+
+impl Storable for Schedule {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+}
+
+impl BoundedStorable for Schedule {
+    const MAX_SIZE: u32 = 4096;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// Thread-local storage for scheduled transfers
+//
+// The queue holds active schedules keyed by (next_run_at, schedule_id), so that the next
+// run is always its first key.
+thread_local! {
+    static SCHEDULES: RefCell<StableBTreeMap<u64, Schedule, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15)))
+    ));
+
+    static SCHEDULE_QUEUE: RefCell<Queue> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16)))
+    ));
+
+    static SCHEDULE_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17))), 0)
+            .expect("Cannot create a schedule ID counter")
+    );
+
+    static TIMER: TimerSlot = const { RefCell::new(None) };
+}
+
+// Helper function to store a schedule, queueing it if it is still active
+fn do_insert_schedule(schedule: &Schedule) {
+    SCHEDULES.with(|s| s.borrow_mut().insert(schedule.id, schedule.clone()));
+    if schedule.status == ScheduleStatus::Active {
+        SCHEDULE_QUEUE.with(|q| {
+            q.borrow_mut()
+                .insert((schedule.next_run_at, schedule.id), ())
+        });
+    }
+}
+
+// Helper function to take a schedule off the queue before it is changed
+fn dequeue(schedule: &Schedule) {
+    SCHEDULE_QUEUE.with(|q| q.borrow_mut().remove(&(schedule.next_run_at, schedule.id)));
+}
+
+/// Sets the timer for the earliest queued run, replacing any timer already set.
+pub fn rearm() {
+    timers::rearm(&TIMER, &SCHEDULE_QUEUE, run_due_schedules);
+}
+
+// Helper function to set the timer for the next schedule, then run every one that is due
+fn run_due_schedules() {
+    let now = time();
+    let due = timers::take_due(
+        &TIMER,
+        &SCHEDULE_QUEUE,
+        now,
+        MAX_RUNS_PER_TICK,
+        run_due_schedules,
+    );
+    for (_, id) in due {
+        if let Some(schedule) = SCHEDULES.with(|s| s.borrow().get(&id)) {
+            run(schedule, now);
+        }
+    }
+    rearm();
+}
+
+// Helper function to decide whether a failed run is worth attempting again
+fn is_retryable(error: &Error) -> bool {
+    !matches!(error, Error::NotFound { .. } | Error::Unauthorized { .. })
+}
+
+// Helper function to move a schedule on to its next run, or complete it
+//
+// Runs missed while the canister was not running are skipped rather than caught up on.
+fn advance(schedule: &mut Schedule, now: u64) {
+    schedule.attempt = 0;
+    let interval = match schedule.interval {
+        Some(interval) => interval,
+        None => {
+            schedule.status = ScheduleStatus::Completed;
+            return;
+        }
+    };
+    let mut due_at = schedule.due_at.saturating_add(interval);
+    if due_at <= now {
+        let missed = (now - due_at) / interval + 1;
+        due_at = due_at.saturating_add(missed.saturating_mul(interval));
+    }
+    let finished = match schedule.end_condition {
+        EndCondition::Never => false,
+        EndCondition::AfterExecutions(count) => schedule.executions >= count,
+        EndCondition::At(end) => due_at > end,
+    };
+    if finished {
+        schedule.status = ScheduleStatus::Completed;
+    } else {
+        schedule.due_at = due_at;
+        schedule.next_run_at = due_at;
+    }
+}
+
+// Helper function to attempt one run of a schedule and record the outcome
+fn run(mut schedule: Schedule, now: u64) {
+    dequeue(&schedule);
+    let payload = TransferPayload {
+        sender_id: schedule.sender_id,
+        receiver_id: schedule.receiver_id,
+        amount: schedule.amount,
+        currency: schedule.currency.clone(),
+        ..Default::default()
+    };
+    match execute_transfer(&schedule.owner, &payload) {
+        Ok(transaction) => {
+            schedule.executions += 1;
+            schedule.last_transaction_id = Some(transaction.id);
+            advance(&mut schedule, now);
+        }
+        Err(error) => {
+            schedule.attempt += 1;
+            let retryable = is_retryable(&error);
+            schedule.failures.push(ScheduleFailure {
+                attempted_at: now,
+                attempt: schedule.attempt,
+                error,
+            });
+            if schedule.failures.len() > MAX_RECORDED_FAILURES {
+                schedule.failures.remove(0);
+            }
+
+            if !retryable {
+                schedule.status = ScheduleStatus::Failed;
+            } else if schedule.attempt < MAX_ATTEMPTS {
+                schedule.next_run_at = now.saturating_add(RETRY_DELAY_NANOS);
+            } else if schedule.interval.is_some() {
+                // Give up on this run but keep the standing order
+                advance(&mut schedule, now);
+            } else {
+                schedule.status = ScheduleStatus::Failed;
+            }
+        }
+    }
+    do_insert_schedule(&schedule);
+}
+
+/// Schedules a transfer that runs at `start_at` and then every `interval` nanoseconds, if set,
+/// until the end condition is met.
+///
+/// Each run is carried out like `transfer_funds` on behalf of the caller. A run that fails is
+/// attempted again up to `MAX_ATTEMPTS` times, `RETRY_DELAY_NANOS` apart.
+#[ic_cdk::update]
+fn schedule_transfer(
+    payload: TransferPayload,
+    start_at: u64,
+    interval: Option<u64>,
+    end_condition: EndCondition,
+) -> Result<Schedule, Error> {
+    // Validate the request before any state is touched
+    ensure_not_anonymous(&caller())?;
+    validation::validate_transfer(&payload)?;
+    if payload.idempotency_key.is_some() || payload.created_at_time.is_some() {
+        return Err(Error::InvalidSchedule {
+            msg: "Scheduled transfers do not take an idempotency key or creation time.".to_string(),
+        });
+    }
+    if payload.conversion.is_some() {
+        return Err(Error::InvalidSchedule {
+            msg: "Scheduled transfers cannot be converted between currencies.".to_string(),
+        });
+    }
+    let currency = currency::check_transfer(&caller(), &payload)?;
+    if interval.is_some_and(|interval| interval < MIN_INTERVAL_NANOS) {
+        return Err(Error::InvalidSchedule {
+            msg: format!(
+                "Interval must be at least {} nanoseconds.",
+                MIN_INTERVAL_NANOS
+            ),
+        });
+    }
+    match end_condition {
+        EndCondition::AfterExecutions(0) => {
+            return Err(Error::InvalidSchedule {
+                msg: "A schedule must run at least once.".to_string(),
+            })
+        }
+        EndCondition::At(end) if end < start_at => {
+            return Err(Error::InvalidSchedule {
+                msg: format!("Schedule ends at {} before it starts at {}.", end, start_at),
+            })
+        }
+        _ => {}
+    }
+
+    // Only the owner of the sender account may schedule transfers out of it
+    match (
+        _get_account(&payload.sender_id),
+        _get_account(&payload.receiver_id),
+    ) {
+        (Some(sender_account), Some(_)) => ensure_owner(&sender_account)?,
+        _ => {
+            return Err(Error::NotFound {
+                msg: "Sender or receiver account not found.".to_string(),
+            })
+        }
+    }
+
+    let id = SCHEDULE_ID_COUNTER
+        .with(|counter| {
+            let current_value = *counter.borrow().get();
+            counter.borrow_mut().set(current_value + 1)
+        })
+        .expect("Cannot increment schedule ID counter");
+    let schedule = Schedule {
+        id,
+        owner: caller(),
+        sender_id: payload.sender_id,
+        receiver_id: payload.receiver_id,
+        amount: payload.amount,
+        currency,
+        interval,
+        end_condition,
+        status: ScheduleStatus::Active,
+        due_at: start_at,
+        next_run_at: start_at,
+        attempt: 0,
+        executions: 0,
+        last_transaction_id: None,
+        failures: Vec::new(),
+        created_at: time(),
+    };
+    do_insert_schedule(&schedule);
+    rearm();
+    Ok(schedule)
+}
+
+/// Retrieves the scheduled transfers owned by the caller, or all of them for staff.
+#[ic_cdk::query]
+fn list_schedules() -> Vec<Schedule> {
+    let caller = caller();
+    let staff = is_staff(&caller);
+    SCHEDULES.with(|s| {
+        s.borrow()
+            .iter()
+            .map(|(_, schedule)| schedule)
+            .filter(|schedule| staff || schedule.owner == caller)
+            .collect()
+    })
+}
+
+/// Cancels a scheduled transfer; only its owner or a teller may do so.
+#[ic_cdk::update]
+fn cancel_schedule(id: u64) -> Result<Schedule, Error> {
+    let mut schedule = SCHEDULES
+        .with(|s| s.borrow().get(&id))
+        .ok_or_else(|| Error::NotFound {
+            msg: format!("Schedule with id={} not found.", id),
+        })?;
+    let caller = caller();
+    if schedule.owner != caller && !has_role(&caller, Role::Teller) {
+        return Err(Error::Unauthorized {
+            msg: format!("Caller may not cancel the schedule with id={}.", id),
+        });
+    }
+    if schedule.status != ScheduleStatus::Active {
+        return Err(Error::InvalidSchedule {
+            msg: format!("Schedule with id={} is no longer active.", id),
+        });
+    }
+
+    dequeue(&schedule);
+    schedule.status = ScheduleStatus::Cancelled;
+    do_insert_schedule(&schedule);
+    rearm();
+    Ok(schedule)
+}
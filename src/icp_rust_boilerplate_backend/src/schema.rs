@@ -0,0 +1,465 @@
+use crate::icrc1::Subaccount;
+use crate::lifecycle::AccountStatus;
+use crate::money::Money;
+use crate::{
+    all_accounts, append_transaction, do_insert_account, escrow, holds, icrc1, icrc3,
+    record_transaction, transactions_in, Account, Memory, StorablePrincipal, Transaction,
+    TransactionKind, ACCOUNT_ADDRESSES, MEMORY_MANAGER, TRANSACTIONS,
+};
+use candid::{Decode, Encode, Principal};
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::Cell;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// Version of the stable memory layout written by this build of the canister.
+///
+/// - 1: the original layout, with `f64` balances and transactions keyed by timestamp.
+/// - 2: versioned `Account` and `Transaction` records, with transactions keyed by ID.
+/// - 3: accounts carry a lifecycle status.
+/// - 4: transactions carry the fee charged on them.
+/// - 5: accounts carry the amount reserved by authorization holds.
+/// - 6: transactions carry a link to the transaction they reverse.
+/// - 7: transactions carry the ID of the batch they were applied in.
+/// - 8: accounts carry balances in other currencies, and transactions carry their currency.
+/// - 9: the index and hash of the last block are stored apart from the block log.
+/// - 10: held escrows are indexed by the accounts of their payer and payee.
+/// - 11: holds are indexed by the account they are placed on.
+pub const SCHEMA_VERSION: u32 = 11;
+
+/// Version tag written in front of every stored `Account`.
+pub const ACCOUNT_VERSION: u8 = 5;
+
+/// Version tag written in front of every stored `Transaction`.
+pub const TRANSACTION_VERSION: u8 = 6;
+
+// Records written before versioning was introduced are bare Candid and start with its magic bytes
+const CANDID_MAGIC: &[u8] = b"DIDL";
+
+// Version of records that carry no version tag
+const UNVERSIONED: u8 = 1;
+
+// Thread-local storage for the version of the stable memory layout
+//
+// Canisters upgraded from the original code have no version stored yet, hence the default of 1;
+// fresh installs record the current version in `init`.
+thread_local! {
+    static STORED_SCHEMA_VERSION: RefCell<Cell<u32, Memory>> = RefCell::new(
+        Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11))), 1)
+            .expect("Cannot create the schema version cell")
+    );
+}
+
+/// Represents an account as stored by schema version 1.
+#[derive(candid::CandidType, Deserialize)]
+struct AccountV1 {
+    id: u64,
+    holder_name: String,
+    balance: f64,
+    created_at: u64,
+}
+
+/// Represents an account as stored by schema version 2.
+#[derive(candid::CandidType, Deserialize)]
+struct AccountV2 {
+    id: u64,
+    owner: Principal,
+    subaccount: Subaccount,
+    holder_name: String,
+    balance: Money,
+    created_at: u64,
+}
+
+/// Represents an account as stored by schema versions 3 and 4.
+#[derive(candid::CandidType, Deserialize)]
+struct AccountV3 {
+    id: u64,
+    owner: Principal,
+    subaccount: Subaccount,
+    holder_name: String,
+    balance: Money,
+    created_at: u64,
+    status: AccountStatus,
+}
+
+/// Represents an account as stored by schema versions 5 to 7.
+#[derive(candid::CandidType, Deserialize)]
+struct AccountV4 {
+    id: u64,
+    owner: Principal,
+    subaccount: Subaccount,
+    holder_name: String,
+    balance: Money,
+    created_at: u64,
+    status: AccountStatus,
+    held: Money,
+}
+
+/// Represents a transaction as stored by schema version 1.
+#[derive(candid::CandidType, Deserialize)]
+struct TransactionV1 {
+    sender_id: u64,
+    receiver_id: u64,
+    amount: f64,
+    timestamp: u64,
+}
+
+// Helper function to convert a legacy floating-point amount to minor units
+fn money_from_f64(amount: f64) -> Money {
+    Money::from_minor_units((amount * 10f64.powi(Money::DECIMALS as i32)).round() as i64)
+}
+
+// Helper function to upgrade an account from the original layout, assigning it to the given owner
+//
+// Until `assign_account_owner` hands it over, the owner is the canister itself.
+fn account_from_v1(legacy: AccountV1, owner: Principal) -> Account {
+    Account {
+        id: legacy.id,
+        owner,
+        subaccount: icrc1::subaccount_from_u64(legacy.id),
+        holder_name: legacy.holder_name,
+        balance: money_from_f64(legacy.balance),
+        created_at: legacy.created_at,
+        status: AccountStatus::Active,
+        held: Money::ZERO,
+        foreign_balances: BTreeMap::new(),
+    }
+}
+
+/// Represents a record stored with a version tag, which is upgraded when it is read.
+pub trait VersionedRecord: Sized {
+    /// Encodes the record with the current version tag.
+    fn encode_record(&self) -> Vec<u8>;
+
+    /// Decodes a stored record of any supported version, reporting why if it cannot.
+    fn decode_record(bytes: &[u8]) -> Result<Self, String>;
+}
+
+/// Prefixes an encoded record with its version tag.
+pub fn encode_versioned(version: u8, payload: Vec<u8>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(payload.len() + 1);
+    bytes.push(version);
+    bytes.extend(payload);
+    bytes
+}
+
+// Helper function to split a stored record into its version and its Candid payload
+fn split_version(bytes: &[u8]) -> Result<(u8, &[u8]), String> {
+    if bytes.starts_with(CANDID_MAGIC) {
+        Ok((UNVERSIONED, bytes))
+    } else {
+        match bytes.split_first() {
+            Some((version, payload)) => Ok((*version, payload)),
+            None => Err("Record is empty".to_string()),
+        }
+    }
+}
+
+// Accounts from version 1 had no owner; they are assigned to the canister itself until an
+// admin hands them over with `assign_account_owner`.
+// Accounts from versions 1 and 2 had no status; they are active.
+// Accounts from versions 1 to 3 had no holds.
+// Accounts from versions 1 to 4 only held the base currency.
+impl VersionedRecord for Account {
+    fn encode_record(&self) -> Vec<u8> {
+        encode_versioned(ACCOUNT_VERSION, Encode!(self).unwrap())
+    }
+
+    fn decode_record(bytes: &[u8]) -> Result<Self, String> {
+        match split_version(bytes)? {
+            (ACCOUNT_VERSION, payload) => Decode!(payload, Account).map_err(|e| e.to_string()),
+            (UNVERSIONED, payload) => {
+                let legacy = Decode!(payload, AccountV1).map_err(|e| e.to_string())?;
+                Ok(account_from_v1(legacy, ic_cdk::id()))
+            }
+            (2, payload) => {
+                let legacy = Decode!(payload, AccountV2).map_err(|e| e.to_string())?;
+                Ok(Account {
+                    id: legacy.id,
+                    owner: legacy.owner,
+                    subaccount: legacy.subaccount,
+                    holder_name: legacy.holder_name,
+                    balance: legacy.balance,
+                    created_at: legacy.created_at,
+                    status: AccountStatus::Active,
+                    held: Money::ZERO,
+                    foreign_balances: BTreeMap::new(),
+                })
+            }
+            (3, payload) => {
+                let legacy = Decode!(payload, AccountV3).map_err(|e| e.to_string())?;
+                Ok(Account {
+                    id: legacy.id,
+                    owner: legacy.owner,
+                    subaccount: legacy.subaccount,
+                    holder_name: legacy.holder_name,
+                    balance: legacy.balance,
+                    created_at: legacy.created_at,
+                    status: legacy.status,
+                    held: Money::ZERO,
+                    foreign_balances: BTreeMap::new(),
+                })
+            }
+            (4, payload) => {
+                let legacy = Decode!(payload, AccountV4).map_err(|e| e.to_string())?;
+                Ok(Account {
+                    id: legacy.id,
+                    owner: legacy.owner,
+                    subaccount: legacy.subaccount,
+                    holder_name: legacy.holder_name,
+                    balance: legacy.balance,
+                    created_at: legacy.created_at,
+                    status: legacy.status,
+                    held: legacy.held,
+                    foreign_balances: BTreeMap::new(),
+                })
+            }
+            (version, _) => Err(format!("Unsupported account record version {}", version)),
+        }
+    }
+}
+
+// Transactions from version 1 have no ID; they are given one when they are re-keyed.
+// Transactions from versions 2 to 5 lack the fee, the reversal link, the batch ID or the
+// currency; Candid decodes the missing optional fields as none, i.e. the base currency.
+impl VersionedRecord for Transaction {
+    fn encode_record(&self) -> Vec<u8> {
+        encode_versioned(TRANSACTION_VERSION, Encode!(self).unwrap())
+    }
+
+    fn decode_record(bytes: &[u8]) -> Result<Self, String> {
+        match split_version(bytes)? {
+            (TRANSACTION_VERSION | 2..=5, payload) => {
+                Decode!(payload, Transaction).map_err(|e| e.to_string())
+            }
+            (UNVERSIONED, payload) => {
+                let legacy = Decode!(payload, TransactionV1).map_err(|e| e.to_string())?;
+                Ok(Transaction {
+                    kind: TransactionKind::Transfer,
+                    sender_id: Some(legacy.sender_id),
+                    receiver_id: Some(legacy.receiver_id),
+                    amount: money_from_f64(legacy.amount),
+                    timestamp: legacy.timestamp,
+                    ..Default::default()
+                })
+            }
+            (version, _) => Err(format!(
+                "Unsupported transaction record version {}",
+                version
+            )),
+        }
+    }
+}
+
+// Helper function to migrate from the original layout
+//
+// Accounts are rewritten with their ICRC-1 address. Legacy transactions are re-keyed by ID in
+// timestamp order and chained into the block log; since they predate the general ledger, each
+// account's balance is then brought onto the books with a single opening deposit, or with an
+// opening withdrawal if the account is overdrawn.
+fn migrate_v1_to_v2() {
+    let accounts = all_accounts();
+    for account in &accounts {
+        do_insert_account(account);
+        ACCOUNT_ADDRESSES.with(|index| {
+            index.borrow_mut().insert(
+                (StorablePrincipal(account.owner), account.subaccount),
+                account.id,
+            )
+        });
+    }
+
+    let legacy = transactions_in(..);
+    TRANSACTIONS.with(|s| {
+        let mut s = s.borrow_mut();
+        let keys: Vec<u64> = s.iter().map(|(key, _)| key).collect();
+        for key in keys {
+            s.remove(&key);
+        }
+    });
+    for transaction in legacy {
+        append_transaction(transaction).expect("Cannot chain a migrated transaction");
+    }
+
+    for account in accounts {
+        let opening = if account.balance > Money::ZERO {
+            Transaction {
+                kind: TransactionKind::Deposit,
+                receiver_id: Some(account.id),
+                amount: account.balance,
+                reference: Some("Migrated balance".to_string()),
+                ..Default::default()
+            }
+        } else if account.balance < Money::ZERO {
+            Transaction {
+                kind: TransactionKind::Withdrawal,
+                sender_id: Some(account.id),
+                amount: Money::ZERO
+                    .checked_sub(account.balance)
+                    .expect("Cannot negate a migrated balance"),
+                reference: Some("Migrated overdrawn balance".to_string()),
+                ..Default::default()
+            }
+        } else {
+            continue;
+        };
+        record_transaction(opening).expect("Cannot book a migrated balance");
+    }
+}
+
+// Helper function to rewrite every account in the current layout, e.g. with its lifecycle
+// status, the amount it has on hold or its balances in other currencies
+fn rewrite_accounts() {
+    for account in all_accounts() {
+        do_insert_account(&account);
+    }
+}
+
+// Helper function for layout changes that need no rewrite, since older records are
+// upgraded when they are read
+fn upgrade_on_read() {}
+
+// Migrations in order; the migration at index i upgrades the layout from version i + 1
+const MIGRATIONS: &[fn()] = &[
+    migrate_v1_to_v2,
+    rewrite_accounts,
+    upgrade_on_read,
+    rewrite_accounts,
+    upgrade_on_read,
+    upgrade_on_read,
+    rewrite_accounts,
+    icrc3::store_tip_of_log,
+    escrow::index_held_escrows,
+    holds::index_holds,
+];
+
+/// Returns the version of the stable memory layout.
+pub fn stored_version() -> u32 {
+    STORED_SCHEMA_VERSION.with(|v| *v.borrow().get())
+}
+
+// Helper function to store the version of the stable memory layout
+fn set_stored_version(version: u32) {
+    STORED_SCHEMA_VERSION
+        .with(|v| v.borrow_mut().set(version))
+        .expect("Cannot store the schema version");
+}
+
+/// Marks the stable memory as using the current layout.
+pub fn record_current_version() {
+    set_stored_version(SCHEMA_VERSION);
+}
+
+/// Runs every migration between the stored layout and the current one.
+///
+/// Traps if the stored layout is newer than this build, so that a downgrade cannot
+/// silently misread data it does not understand.
+pub fn migrate() {
+    let mut version = stored_version();
+    if version > SCHEMA_VERSION {
+        panic!(
+            "Stable memory uses schema version {}, this build only supports up to {}",
+            version, SCHEMA_VERSION
+        );
+    }
+    while version < SCHEMA_VERSION {
+        MIGRATIONS[(version - 1) as usize]();
+        version += 1;
+        set_stored_version(version);
+    }
+}
+
+/// Returns the version of the stable memory layout.
+#[ic_cdk::query]
+fn get_schema_version() -> u32 {
+    stored_version()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[1, 2, 3])
+    }
+
+    #[test]
+    fn original_account_is_upgraded_for_the_given_owner() {
+        let legacy = AccountV1 {
+            id: 7,
+            holder_name: "Alice".to_string(),
+            balance: 12.34,
+            created_at: 42,
+        };
+        let account = account_from_v1(legacy, owner());
+        assert_eq!(account.id, 7);
+        assert_eq!(account.owner, owner());
+        assert_eq!(account.subaccount, icrc1::subaccount_from_u64(7));
+        assert_eq!(account.holder_name, "Alice");
+        assert_eq!(account.balance, Money::from_minor_units(1_234));
+        assert_eq!(account.created_at, 42);
+        assert!(matches!(account.status, AccountStatus::Active));
+        assert_eq!(account.held, Money::ZERO);
+        assert!(account.foreign_balances.is_empty());
+    }
+
+    #[test]
+    fn legacy_amounts_round_to_the_nearest_minor_unit() {
+        assert_eq!(money_from_f64(0.1 + 0.2), Money::from_minor_units(30));
+        assert_eq!(money_from_f64(-5.25), Money::from_minor_units(-525));
+        assert_eq!(money_from_f64(0.0), Money::ZERO);
+    }
+
+    #[test]
+    fn unversioned_records_are_recognised_by_their_candid_header() {
+        let bytes = Encode!(&AccountV1 {
+            id: 1,
+            holder_name: "Bob".to_string(),
+            balance: 1.0,
+            created_at: 0,
+        })
+        .unwrap();
+        let (version, payload) = split_version(&bytes).unwrap();
+        assert_eq!(version, UNVERSIONED);
+        assert_eq!(payload, bytes.as_slice());
+        assert!(split_version(&[]).is_err());
+    }
+
+    #[test]
+    fn version_2_account_decodes_as_active_without_holds() {
+        let legacy = AccountV2 {
+            id: 3,
+            owner: owner(),
+            subaccount: icrc1::subaccount_from_u64(9),
+            holder_name: "Carol".to_string(),
+            balance: Money::from_minor_units(500),
+            created_at: 10,
+        };
+        let bytes = encode_versioned(2, Encode!(&legacy).unwrap());
+        let account = Account::decode_record(&bytes).unwrap();
+        assert_eq!(account.id, 3);
+        assert_eq!(account.owner, owner());
+        assert_eq!(account.subaccount, icrc1::subaccount_from_u64(9));
+        assert_eq!(account.balance, Money::from_minor_units(500));
+        assert!(matches!(account.status, AccountStatus::Active));
+        assert_eq!(account.held, Money::ZERO);
+    }
+
+    #[test]
+    fn current_account_round_trips() {
+        let account = account_from_v1(
+            AccountV1 {
+                id: 5,
+                holder_name: "Dan".to_string(),
+                balance: 3.5,
+                created_at: 1,
+            },
+            owner(),
+        );
+        let decoded = Account::decode_record(&account.encode_record()).unwrap();
+        assert_eq!(decoded.id, 5);
+        assert_eq!(decoded.owner, owner());
+        assert_eq!(decoded.balance, Money::from_minor_units(350));
+        assert!(Account::decode_record(&[99, 0]).is_err());
+    }
+}
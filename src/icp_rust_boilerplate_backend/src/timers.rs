@@ -0,0 +1,69 @@
+use crate::Memory;
+use ic_cdk::api::time;
+use ic_cdk_timers::{clear_timer, set_timer, TimerId};
+use ic_stable_structures::StableBTreeMap;
+use std::cell::RefCell;
+use std::ops::Bound;
+use std::thread::LocalKey;
+use std::time::Duration;
+
+/// Represents a queue of entries keyed by (due time, ID), so that the next one due is always
+/// the first key.
+pub type Queue = StableBTreeMap<(u64, u64), (), Memory>;
+
+/// Represents the slot holding the timer set for a queue.
+///
+/// Timers do not survive upgrades, so every queue is armed again in `post_upgrade`.
+pub type TimerSlot = RefCell<Option<TimerId>>;
+
+/// Sets the timer for the first entry of a queue, replacing any timer already set.
+pub fn rearm(
+    slot: &'static LocalKey<TimerSlot>,
+    queue: &'static LocalKey<RefCell<Queue>>,
+    callback: fn(),
+) {
+    let next = queue.with(|q| q.borrow().first_key_value().map(|((at, _), _)| at));
+    arm(slot, next, callback);
+}
+
+/// Returns up to `limit` entries of a queue that are due at `now`, earliest first, after
+/// setting the timer for the entry that follows them.
+///
+/// The timer is set before the due entries are processed, so that the queue keeps being served
+/// whatever happens to them. Callers still rearm once done, since processing may queue entries
+/// that fall due sooner.
+pub fn take_due(
+    slot: &'static LocalKey<TimerSlot>,
+    queue: &'static LocalKey<RefCell<Queue>>,
+    now: u64,
+    limit: usize,
+    callback: fn(),
+) -> Vec<(u64, u64)> {
+    let (due, next) = queue.with(|q| {
+        let q = q.borrow();
+        let due: Vec<(u64, u64)> = q
+            .range(..=(now, u64::MAX))
+            .take(limit)
+            .map(|(key, _)| key)
+            .collect();
+        let next = match due.last() {
+            Some(last) => q.range((Bound::Excluded(*last), Bound::Unbounded)).next(),
+            None => q.first_key_value(),
+        };
+        (due, next.map(|((at, _), _)| at))
+    });
+    arm(slot, next, callback);
+    due
+}
+
+// Helper function to replace the timer of a queue with one firing at the given time, if any
+fn arm(slot: &'static LocalKey<TimerSlot>, due_at: Option<u64>, callback: fn()) {
+    if let Some(timer) = slot.with(|t| t.borrow_mut().take()) {
+        clear_timer(timer);
+    }
+    if let Some(due_at) = due_at {
+        let delay = Duration::from_nanos(due_at.saturating_sub(time()));
+        let timer = set_timer(delay, callback);
+        slot.with(|t| *t.borrow_mut() = Some(timer));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::money::Money;
+use crate::{Error, TransferPayload};
+
+/// Maximum length of an account holder name in bytes, chosen so that an `Account`
+/// always fits within its `BoundedStorable::MAX_SIZE`.
+pub const MAX_HOLDER_NAME_LEN: usize = 256;
+
+/// Maximum length of a deposit or withdrawal reference in bytes.
+pub const MAX_REFERENCE_LEN: usize = 128;
+
+/// Maximum length of a client-supplied idempotency key in bytes.
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 64;
+
+/// Checks that an amount being moved is strictly positive.
+pub fn validate_amount(amount: Money) -> Result<(), Error> {
+    if amount > Money::ZERO {
+        Ok(())
+    } else {
+        Err(Error::InvalidAmount {
+            msg: format!("Amount must be positive, got {}.", amount),
+        })
+    }
+}
+
+/// Checks that an opening balance is not negative.
+pub fn validate_initial_balance(amount: Money) -> Result<(), Error> {
+    if amount >= Money::ZERO {
+        Ok(())
+    } else {
+        Err(Error::InvalidAmount {
+            msg: format!("Initial balance must not be negative, got {}.", amount),
+        })
+    }
+}
+
+/// Checks that a holder name is non-blank and short enough to be stored.
+pub fn validate_holder_name(name: &str) -> Result<(), Error> {
+    if name.trim().is_empty() {
+        return Err(Error::InvalidName {
+            msg: "Holder name must not be empty.".to_string(),
+        });
+    }
+    if name.len() > MAX_HOLDER_NAME_LEN {
+        return Err(Error::NameTooLong {
+            msg: format!(
+                "Holder name is {} bytes long, the maximum is {}.",
+                name.len(),
+                MAX_HOLDER_NAME_LEN
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Checks that a deposit or withdrawal reference is short enough to be stored.
+pub fn validate_reference(reference: &str) -> Result<(), Error> {
+    if reference.len() > MAX_REFERENCE_LEN {
+        return Err(Error::InvalidReference {
+            msg: format!(
+                "Reference is {} bytes long, the maximum is {}.",
+                reference.len(),
+                MAX_REFERENCE_LEN
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Checks that an idempotency key is non-blank, short enough and paired with a creation time.
+pub fn validate_idempotency_key(key: &str, created_at_time: Option<u64>) -> Result<(), Error> {
+    if key.trim().is_empty() {
+        return Err(Error::InvalidIdempotencyKey {
+            msg: "Idempotency key must not be empty.".to_string(),
+        });
+    }
+    if key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+        return Err(Error::InvalidIdempotencyKey {
+            msg: format!(
+                "Idempotency key is {} bytes long, the maximum is {}.",
+                key.len(),
+                MAX_IDEMPOTENCY_KEY_LEN
+            ),
+        });
+    }
+    if created_at_time.is_none() {
+        return Err(Error::InvalidIdempotencyKey {
+            msg: "Idempotency key requires created_at_time to be set.".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Checks a transfer payload before any account is read or modified.
+pub fn validate_transfer(payload: &TransferPayload) -> Result<(), Error> {
+    if payload.sender_id == payload.receiver_id {
+        return Err(Error::SelfTransfer {
+            msg: format!(
+                "Cannot transfer from account with id={} to itself.",
+                payload.sender_id
+            ),
+        });
+    }
+    if let Some(key) = &payload.idempotency_key {
+        validate_idempotency_key(key, payload.created_at_time)?;
+    }
+    validate_amount(payload.amount)
+}